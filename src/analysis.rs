@@ -0,0 +1,220 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    actions::Action,
+    net::{Activation, NetGenome, NeuralLink, NeuralSource, NeuralTarget, MAX_WEIGHT},
+    pool::Allele,
+};
+
+/// Structural report on a `NetGenome`, see `NetGenome::analyze`.
+#[derive(Clone, Debug, Default)]
+pub struct Analysis {
+    /// Nodes reached, directly or through hidden neurons, by a sensor.
+    pub fed: HashSet<NeuralTarget>,
+    /// Nodes whose output reaches an action, actions included.
+    pub contributing: HashSet<NeuralTarget>,
+    /// Actions whose neuron can never reach its firing threshold.
+    pub silent: Vec<Action>,
+    /// Upper bound of what each link can add to its target's charge per tick.
+    pub contributions: HashMap<(NeuralSource, NeuralTarget), f32>,
+}
+
+impl Analysis {
+    /// Links whose contribution never exceeds `epsilon`.
+    pub fn negligible(&self, epsilon: f32) -> Vec<(NeuralSource, NeuralTarget)> {
+        self.contributions
+            .iter()
+            .filter(|(_, c)| **c < epsilon)
+            .map(|(link, _)| link.clone())
+            .collect()
+    }
+}
+
+/// Bounds of the value a source can feed into a link.
+fn source_range(genome: &NetGenome, source: &NeuralSource) -> (f32, f32) {
+    match source {
+        // Every sensor is normalised to [-1, 1].
+        NeuralSource::Sensor(_) => (-1.0, 1.0),
+        NeuralSource::Hidden(hid) => match genome.nodes.get(&NeuralTarget::Hidden(*hid)) {
+            Some(node) => match node.params.activation {
+                Activation::Tanh | Activation::Sine => (-1.0, 1.0),
+                Activation::Sigmoid | Activation::Step | Activation::Gaussian => (0.0, 1.0),
+                Activation::Relu => (0.0, f32::INFINITY),
+            },
+            None => (0.0, 0.0),
+        },
+    }
+}
+
+/// Largest absolute output of `activation` for an input in `[lo, hi]`.
+fn peak(activation: Activation, lo: f32, hi: f32) -> f32 {
+    let ends = activation.apply(lo).abs().max(activation.apply(hi).abs());
+    match activation {
+        // Monotonic: the extremes are at the ends.
+        Activation::Tanh | Activation::Sigmoid | Activation::Relu | Activation::Step => ends,
+        Activation::Gaussian if lo <= 0.0 && hi >= 0.0 => 1.0,
+        Activation::Gaussian => ends,
+        // |sin| peaks at every odd multiple of pi / 2.
+        Activation::Sine => {
+            let half_pi = std::f32::consts::FRAC_PI_2;
+            let next_peak =
+                half_pi + ((lo - half_pi) / std::f32::consts::PI).ceil() * std::f32::consts::PI;
+            if next_peak <= hi {
+                1.0
+            } else {
+                ends
+            }
+        }
+    }
+}
+
+fn link_bound(link: &NeuralLink, (lo, hi): (f32, f32)) -> f32 {
+    let weight = if link.plastic.is_some() {
+        MAX_WEIGHT
+    } else {
+        link.weight.abs()
+    };
+    let (lo, hi) = if link.inverse {
+        (1.0 - hi, 1.0 - lo)
+    } else {
+        (lo, hi)
+    };
+    let x = lo.abs().max(hi.abs());
+    if x.is_infinite() {
+        return 1.0;
+    }
+    (x * weight).tanh()
+}
+
+impl NetGenome {
+    pub fn analyze(&self) -> Analysis {
+        let mut analysis = Analysis::default();
+
+        // Forward pass from the sensors.
+        loop {
+            let before = analysis.fed.len();
+            for (target, node) in &self.nodes {
+                let fed = node.inputs.keys().any(|source| match source {
+                    NeuralSource::Sensor(_) => true,
+                    NeuralSource::Hidden(hid) => analysis.fed.contains(&NeuralTarget::Hidden(*hid)),
+                });
+                if fed {
+                    analysis.fed.insert(target.clone());
+                }
+            }
+            if analysis.fed.len() == before {
+                break;
+            }
+        }
+
+        // Backward pass from the actions.
+        let mut queue: Vec<_> = self
+            .nodes
+            .keys()
+            .filter(|target| matches!(target, NeuralTarget::Action(_)))
+            .cloned()
+            .collect();
+        while let Some(target) = queue.pop() {
+            if analysis.contributing.insert(target.clone()) {
+                if let Some(node) = self.nodes.get(&target) {
+                    queue.extend(node.get_gene_requirements());
+                }
+            }
+        }
+
+        for (target, node) in &self.nodes {
+            let fan_in = node.inputs.len().max(1) as f32;
+            let mut total = 0.0;
+            for (source, link) in &node.inputs {
+                let bound = link_bound(link, source_range(self, source)) / fan_in;
+                total += bound;
+                analysis
+                    .contributions
+                    .insert((source.clone(), target.clone()), bound);
+            }
+
+            if let NeuralTarget::Action(action) = target {
+                let params = node.params;
+                if params.leak >= 1.0 {
+                    // Without leak the charge can grow without bound.
+                    continue;
+                }
+                let charge = total / (1.0 - params.leak);
+                let (lo, hi) = (params.bias - charge, params.bias + charge);
+                if peak(params.activation, lo, hi) < params.threshold {
+                    analysis.silent.push(*action);
+                }
+            }
+        }
+        analysis
+    }
+
+    /// Returns the genome stripped of actions that can never fire and of the
+    /// hidden neurons left without a purpose. Links whose contribution bound
+    /// is below `epsilon` are dropped as well: since a node averages its
+    /// inputs this is only an approximation, pass `0.0` for an exact result.
+    pub fn simplified(&self, epsilon: f32) -> NetGenome {
+        let mut genome = self.clone();
+        let analysis = self.analyze();
+        for action in &analysis.silent {
+            genome.nodes.remove(&NeuralTarget::Action(*action));
+        }
+        for (source, target) in analysis.negligible(epsilon) {
+            if let Some(node) = genome.nodes.get_mut(&target) {
+                node.inputs.remove(&source);
+            }
+        }
+        genome.repair();
+        genome
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        input::Sensor,
+        net::{NeuralNode, NeuronParams},
+    };
+
+    fn genome(leak: f32, bias: f32) -> NetGenome {
+        let mut link = NeuralLink::new();
+        link.inverse = false;
+        link.weight = 4.0;
+        link.plastic = None;
+        let mut node = NeuralNode::new();
+        node.inputs.clear();
+        node.inputs
+            .insert(NeuralSource::Sensor(Sensor::Alive), link);
+        node.params = NeuronParams {
+            activation: Activation::Gaussian,
+            leak,
+            threshold: 0.9,
+            bias,
+        };
+        let mut genome = NetGenome::default();
+        genome
+            .nodes
+            .insert(NeuralTarget::Action(Action::IncX), node);
+        genome
+    }
+
+    #[test]
+    fn gaussian_with_a_large_leak_is_not_silent() {
+        // The charge spans about +-100, so the peak at -1.56 is narrower than
+        // any even sampling of that range would catch.
+        let genome = genome(0.99, 1.56);
+        assert!(genome.analyze().silent.is_empty());
+        assert!(genome
+            .simplified(0.0)
+            .nodes
+            .contains_key(&NeuralTarget::Action(Action::IncX)));
+    }
+
+    #[test]
+    fn gaussian_out_of_reach_is_silent() {
+        // The charge spans about +-2, far from the peak at -10.
+        let genome = genome(0.5, 10.0);
+        assert_eq!(genome.analyze().silent, vec![Action::IncX]);
+    }
+}
@@ -0,0 +1,42 @@
+use serde::{Deserialize, Serialize};
+
+use crate::net::NetGenome;
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Champion {
+    pub generation: usize,
+    pub pool: usize,
+    pub score: f32,
+    pub genome: NetGenome,
+}
+
+/// Most champions kept per pool.
+pub const MAX_PER_POOL: usize = 200;
+
+/// The best genome of every pool over a run. Once a pool has more than
+/// `MAX_PER_POOL` champions, every other one is dropped, so the archive
+/// stays evenly spread over the run at a coarser interval.
+#[derive(Default, Clone, Serialize, Deserialize)]
+pub struct HallOfFame {
+    pub champions: Vec<Champion>,
+}
+
+impl HallOfFame {
+    pub fn record(&mut self, champion: Champion) {
+        let pool = champion.pool;
+        self.champions.push(champion);
+        if self.of_pool(pool).count() > MAX_PER_POOL {
+            let mut i = 0;
+            self.champions.retain(|c| {
+                if c.pool != pool {
+                    return true;
+                }
+                i += 1;
+                i % 2 == 1
+            });
+        }
+    }
+    pub fn of_pool(&self, pool: usize) -> impl Iterator<Item = &Champion> {
+        self.champions.iter().filter(move |c| c.pool == pool)
+    }
+}
@@ -0,0 +1,190 @@
+use std::{cmp::Ordering, collections::HashMap, str::FromStr};
+
+use rand::random;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{net::NetGenome, replicant::Replicant, world::World};
+
+/// What a behaviour descriptor summarises of a replicant's round.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Descriptor {
+    /// Position at the end of the round.
+    Position,
+    /// Share of the ticks spent on a new cell, and the centre of the cells
+    /// visited.
+    Visited,
+    /// How often each action fired.
+    Actions,
+}
+
+impl Default for Descriptor {
+    fn default() -> Self {
+        Descriptor::Position
+    }
+}
+
+/// Parses `position`, `visited` or `actions`.
+impl FromStr for Descriptor {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "position" => Ok(Descriptor::Position),
+            "visited" => Ok(Descriptor::Visited),
+            "actions" => Ok(Descriptor::Actions),
+            _ => Err(format!("unknown descriptor {:?}", s)),
+        }
+    }
+}
+
+impl Descriptor {
+    /// Describes the round of `rep`, every value between 0 and 1.
+    pub fn describe(&self, rep: &Replicant, world: &World) -> Vec<f32> {
+        let w = world.width.max(1) as f32;
+        let h = world.height.max(1) as f32;
+        let behaviour = match self {
+            Descriptor::Position => vec![rep.pos.0 as f32 / w, rep.pos.1 as f32 / h],
+            Descriptor::Visited => {
+                let n = rep.visited.len().max(1) as f32;
+                let (x, y) = rep.visited.iter().fold((0.0, 0.0), |(x, y), pos| {
+                    (x + pos.0 as f32, y + pos.1 as f32)
+                });
+                vec![n / (rep.time + 1) as f32, x / n / w, y / n / h]
+            }
+            Descriptor::Actions => {
+                let total = rep.actions.iter().sum::<usize>().max(1) as f32;
+                rep.actions.iter().map(|n| *n as f32 / total).collect()
+            }
+        };
+        behaviour.into_iter().map(|v| v.clamp(0.0, 1.0)).collect()
+    }
+}
+
+fn distance(a: &[f32], b: &[f32]) -> f32 {
+    a.iter()
+        .zip(b)
+        .map(|(x, y)| (x - y) * (x - y))
+        .sum::<f32>()
+        .sqrt()
+}
+
+/// Behaviours seen in past generations, against which novelty is measured.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct NoveltyArchive {
+    pub behaviours: Vec<Vec<f32>>,
+    /// Neighbours the novelty is averaged over.
+    pub k: usize,
+    /// Probability of archiving each evaluated behaviour.
+    pub add_rate: f32,
+    /// Oldest behaviours are forgotten past this size.
+    pub max_size: usize,
+}
+
+impl Default for NoveltyArchive {
+    fn default() -> Self {
+        Self {
+            behaviours: vec![],
+            k: 15,
+            add_rate: 0.01,
+            max_size: 5000,
+        }
+    }
+}
+
+impl NoveltyArchive {
+    /// Mean distance of each behaviour to its `k` nearest neighbours among
+    /// the other behaviours and the archive.
+    pub fn novelty(&self, behaviours: &[Vec<f32>]) -> Vec<f32> {
+        behaviours
+            .par_iter()
+            .enumerate()
+            .map(|(i, behaviour)| {
+                let mut distances: Vec<f32> = behaviours
+                    .iter()
+                    .enumerate()
+                    .filter(|(j, _)| *j != i)
+                    .map(|(_, other)| other)
+                    .chain(&self.behaviours)
+                    .map(|other| distance(behaviour, other))
+                    .collect();
+                let k = self.k.max(1).min(distances.len());
+                if k == 0 {
+                    return 0.0;
+                }
+                distances.select_nth_unstable_by(k - 1, |a, b| {
+                    a.partial_cmp(b).unwrap_or(Ordering::Equal)
+                });
+                distances[..k].iter().sum::<f32>() / k as f32
+            })
+            .collect()
+    }
+    pub fn archive(&mut self, behaviours: &[Vec<f32>]) {
+        for behaviour in behaviours {
+            if random::<f32>() < self.add_rate {
+                self.behaviours.push(behaviour.clone());
+            }
+        }
+        if self.behaviours.len() > self.max_size {
+            let excess = self.behaviours.len() - self.max_size;
+            self.behaviours.drain(..excess);
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Elite {
+    pub genome: NetGenome,
+    pub fitness: f32,
+    pub behaviour: Vec<f32>,
+}
+
+/// Grid over the behaviour space keeping the fittest genome of each cell,
+/// with a separate grid per pool.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct EliteGrid {
+    /// Cells along each dimension of the behaviour.
+    pub bins: usize,
+    pub cells: HashMap<Vec<usize>, Elite>,
+}
+
+impl EliteGrid {
+    pub fn new(bins: usize) -> Self {
+        Self {
+            bins,
+            cells: HashMap::new(),
+        }
+    }
+    /// Pool followed by the bin of each value of the behaviour.
+    pub fn cell(&self, pool: usize, behaviour: &[f32]) -> Vec<usize> {
+        let bins = self.bins.max(1);
+        std::iter::once(pool)
+            .chain(
+                behaviour
+                    .iter()
+                    .map(|v| ((v * bins as f32) as usize).min(bins - 1)),
+            )
+            .collect()
+    }
+    /// Keeps `genome` if its cell is empty or holds a less fit genome.
+    pub fn insert(&mut self, genome: &NetGenome, fitness: f32, behaviour: Vec<f32>) -> bool {
+        let cell = self.cell(genome.pool(), &behaviour);
+        if let Some(elite) = self.cells.get(&cell) {
+            if elite.fitness >= fitness {
+                return false;
+            }
+        }
+        self.cells.insert(
+            cell,
+            Elite {
+                genome: genome.clone(),
+                fitness,
+                behaviour,
+            },
+        );
+        true
+    }
+    /// Sum of the fitness of all elites.
+    pub fn qd_score(&self) -> f32 {
+        self.cells.values().map(|e| e.fitness).sum()
+    }
+}
@@ -0,0 +1,159 @@
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{fitness::FitnessFn, server::Server};
+
+/// Two pools evolving against each other, each one's fitness depending on
+/// how often it meets the other. Meeting means being on a neighbouring cell.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Scenario {
+    /// Predators catch the prey they meet, which leaves the world for the
+    /// rest of the round. Predators score by captures, prey by the time they
+    /// stayed free.
+    PredatorPrey { predator: usize, prey: usize },
+    /// Parasites score by time spent next to hosts, hosts by surviving and
+    /// keeping away from parasites. Nobody is caught.
+    HostParasite { host: usize, parasite: usize },
+}
+
+/// Parses `predator-prey[:<predator>,<prey>]` or
+/// `host-parasite[:<host>,<parasite>]`, pools 0 and 1 by default.
+impl FromStr for Scenario {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, arg) = s.split_once(':').unwrap_or((s, "0,1"));
+        let pools = arg
+            .split(',')
+            .map(|n| {
+                n.trim()
+                    .parse::<usize>()
+                    .map_err(|e| format!("{:?}: {}", n, e))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let (a, b) = match pools[..] {
+            [a, b] if a != b => (a, b),
+            _ => return Err(format!("expected two different pools, got {:?}", arg)),
+        };
+        match name {
+            "predator-prey" => Ok(Scenario::PredatorPrey {
+                predator: a,
+                prey: b,
+            }),
+            "host-parasite" => Ok(Scenario::HostParasite {
+                host: a,
+                parasite: b,
+            }),
+            _ => Err(format!("unknown scenario {:?}", name)),
+        }
+    }
+}
+
+impl Scenario {
+    /// The two pools of the scenario.
+    pub fn pools(&self) -> [usize; 2] {
+        match *self {
+            Scenario::PredatorPrey { predator, prey } => [predator, prey],
+            Scenario::HostParasite { host, parasite } => [host, parasite],
+        }
+    }
+    /// Pool whose members `pool` counts contacts with.
+    pub fn opponent(&self, pool: usize) -> Option<usize> {
+        match self.pools() {
+            [a, b] if pool == a => Some(b),
+            [a, b] if pool == b => Some(a),
+            _ => None,
+        }
+    }
+    /// Pools of predators and of the prey they catch on contact, if any.
+    pub fn hunt(&self) -> Option<(usize, usize)> {
+        match *self {
+            Scenario::PredatorPrey { predator, prey } => Some((predator, prey)),
+            Scenario::HostParasite { .. } => None,
+        }
+    }
+    /// Fitness function of each pool of the scenario.
+    pub fn fitness(&self) -> [(usize, FitnessFn); 2] {
+        match *self {
+            Scenario::PredatorPrey { predator, prey } => {
+                [(predator, FitnessFn::Captures), (prey, FitnessFn::Evasion)]
+            }
+            Scenario::HostParasite { host, parasite } => [
+                (
+                    host,
+                    FitnessFn::Weighted(vec![
+                        (0.5, FitnessFn::Survival),
+                        (0.5, FitnessFn::Evasion),
+                    ]),
+                ),
+                (parasite, FitnessFn::Contact),
+            ],
+        }
+    }
+}
+
+/// How the current members of a pool fare against archived champions of
+/// their opponent, to tell an arms race from cycling.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ArmsRace {
+    pub pool: usize,
+    pub opponent: usize,
+    /// Mean fitness of the pool against the current opponents.
+    pub current: f32,
+    /// Mean fitness of the pool against the opponent champion of each
+    /// sampled generation, oldest first.
+    pub past: Vec<(usize, f32)>,
+}
+
+/// Cross-evaluates every pool of the scenario against up to `samples`
+/// evenly spaced champions of its opponent from the hall of fame.
+pub fn arms_race(server: &Server, scenario: &Scenario, samples: usize) -> Vec<ArmsRace> {
+    scenario
+        .pools()
+        .iter()
+        .map(|pool| {
+            let opponent = scenario.opponent(*pool).unwrap();
+            // Averaged over the episodes of the round.
+            let scores: Vec<f32> = server
+                .sim
+                .replicants
+                .iter()
+                .zip(server.scores())
+                .filter(|(rep, _)| rep.net.pool() == *pool)
+                .map(|(_, score)| score)
+                .collect();
+            let current = scores.iter().sum::<f32>() / scores.len().max(1) as f32;
+            let champions: Vec<_> = server.hall_of_fame.of_pool(opponent).collect();
+            let step = (champions.len() / samples.max(1)).max(1);
+            let past = champions
+                .iter()
+                .rev()
+                .step_by(step)
+                .take(samples)
+                .rev()
+                .map(|champion| {
+                    let score = server.evaluate_against(&champion.genome, opponent, *pool);
+                    (champion.generation, score)
+                })
+                .collect();
+            ArmsRace {
+                pool: *pool,
+                opponent,
+                current,
+                past,
+            }
+        })
+        .collect()
+}
+
+impl ArmsRace {
+    pub fn print(&self) {
+        println!(
+            "pool {} against pool {}: {:.3} today",
+            self.pool, self.opponent, self.current
+        );
+        for (generation, score) in &self.past {
+            println!("  generation {}: {:.3}", generation, score);
+        }
+    }
+}
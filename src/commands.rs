@@ -0,0 +1,281 @@
+use std::path::{Path, PathBuf};
+
+use structopt::StructOpt;
+
+use crate::{coevolution, export::Format, legacy, net::NetGenome, report, server::Server};
+
+#[derive(Debug, StructOpt)]
+pub enum Command {
+    /// Export the champion genome of each pool of a save file as a graph
+    Export {
+        file: PathBuf,
+        /// dot, mermaid or json
+        #[structopt(long, default_value = "dot")]
+        format: Format,
+        /// Strip dead structure before exporting
+        #[structopt(long)]
+        simplify: bool,
+        /// Directory to write to, next to the save file by default
+        #[structopt(long)]
+        out: Option<PathBuf>,
+    },
+    /// Inject the genomes found in a directory into the gene pools of a save.
+    /// This rewrites the save, so stop the process running it first or pass
+    /// --running
+    Import {
+        file: PathBuf,
+        dir: PathBuf,
+        /// Pool to inject into, by default the pool of each genome
+        #[structopt(long)]
+        pool: Option<usize>,
+        /// Hand the genomes to the process running the save instead, through
+        /// `{file}.import/`, which it reads at the end of each round
+        #[structopt(long)]
+        running: bool,
+    },
+    /// List the hall of fame of a save
+    Hof {
+        file: PathBuf,
+        #[structopt(long)]
+        pool: Option<usize>,
+        /// Also write each listed champion as a JSON genome to this directory
+        #[structopt(long)]
+        export: Option<PathBuf>,
+    },
+    /// Describe the alleles held by the gene pools of a save
+    Report {
+        file: PathBuf,
+        #[structopt(long)]
+        pool: Option<usize>,
+        /// Most evaluated genes to list per pool
+        #[structopt(long, default_value = "10")]
+        top: usize,
+        /// Print the reports as JSON
+        #[structopt(long)]
+        json: bool,
+    },
+    /// Show how the gene pools changed between two saves of a run
+    Diff {
+        old: PathBuf,
+        new: PathBuf,
+        #[structopt(long)]
+        pool: Option<usize>,
+    },
+    /// Re-evaluate archived champions against the population of a save
+    HofEval {
+        file: PathBuf,
+        #[structopt(long)]
+        pool: Option<usize>,
+        /// Only evaluate every n-th generation
+        #[structopt(long, default_value = "1")]
+        every: usize,
+        /// Members of the champion's pool replaced by copies of it
+        #[structopt(long, default_value = "100")]
+        copies: usize,
+    },
+    /// Play the pools of a co-evolution scenario against archived champions
+    /// of their opponent
+    ArmsRace {
+        file: PathBuf,
+        /// Archived opponents to play against, evenly spaced over the run
+        #[structopt(long, default_value = "10")]
+        samples: usize,
+    },
+}
+
+/// Reads a save, converting it from an older format if needed. `None` if
+/// the file is missing or not a save.
+pub fn load(path: &Path) -> Option<Server> {
+    let content = std::fs::read(path).ok()?;
+    let server = bincode::deserialize(&content)
+        .ok()
+        .or_else(|| legacy::migrate(&content));
+    if server.is_none() {
+        eprintln!("unrecognised save file {}", path.to_string_lossy());
+    }
+    server
+}
+
+/// Reads every `*.json` genome in `dir`, skipping the files that are not
+/// genomes.
+pub fn load_genomes(dir: &Path) -> Vec<NetGenome> {
+    let mut paths: Vec<_> = std::fs::read_dir(dir)
+        .unwrap_or_else(|e| {
+            eprintln!("cannot read {}: {}", dir.to_string_lossy(), e);
+            std::process::exit(1);
+        })
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().map_or(false, |ext| ext == "json"))
+        .collect();
+    paths.sort();
+    paths
+        .iter()
+        .filter_map(|path| {
+            let json = std::fs::read_to_string(path).ok()?;
+            match NetGenome::from_json(&json) {
+                Ok(genome) => Some(genome),
+                Err(e) => {
+                    eprintln!("skipping {}: {}", path.to_string_lossy(), e);
+                    None
+                }
+            }
+        })
+        .collect()
+}
+
+fn load_or_exit(path: &Path) -> Server {
+    load(path).unwrap_or_else(|| {
+        eprintln!("cannot read {}", path.to_string_lossy());
+        std::process::exit(1);
+    })
+}
+
+impl Command {
+    pub fn run(self) {
+        match self {
+            Command::Export {
+                file,
+                format,
+                simplify,
+                out,
+            } => {
+                let server = load_or_exit(&file);
+                let name = file.file_name().unwrap().to_string_lossy().to_string();
+                let dir = out
+                    .unwrap_or_else(|| file.parent().map(|p| p.to_path_buf()).unwrap_or_default());
+                for (pool, genome) in server.champions() {
+                    let genome = if simplify {
+                        genome.simplified(0.0)
+                    } else {
+                        genome
+                    };
+                    let path = dir.join(format!("{}.pool-{}.{}", name, pool, format.extension()));
+                    std::fs::write(&path, format.render(&genome)).unwrap();
+                    println!("{}", path.to_string_lossy());
+                }
+            }
+            Command::Import {
+                file,
+                dir,
+                pool,
+                running,
+            } if running => {
+                let genomes = load_genomes(&dir);
+                let import_dir = Server::import_dir(&file);
+                std::fs::create_dir_all(&import_dir).unwrap();
+                for (i, genome) in genomes.iter().enumerate() {
+                    let mut genome = genome.clone();
+                    if let Some(pool) = pool {
+                        genome.set_pool(pool);
+                    }
+                    // Written under another name first so the running
+                    // process never reads a partial genome.
+                    let path = import_dir.join(format!("{}-{}.json", std::process::id(), i));
+                    let tmp_file = format!("{}-tmp", path.to_string_lossy());
+                    std::fs::write(&tmp_file, genome.to_json()).unwrap();
+                    std::fs::rename(&tmp_file, &path).unwrap();
+                }
+                println!(
+                    "queued {} genomes in {}",
+                    genomes.len(),
+                    import_dir.to_string_lossy()
+                );
+            }
+            Command::Import {
+                file, dir, pool, ..
+            } => {
+                let mut server = load_or_exit(&file);
+                let genomes = load_genomes(&dir);
+                for genome in &genomes {
+                    server.inject(genome, pool.unwrap_or_else(|| genome.pool()));
+                }
+                let tmp_file = format!("{}-tmp", &file.to_string_lossy());
+                std::fs::write(&tmp_file, bincode::serialize(&server).unwrap()).unwrap();
+                std::fs::rename(&tmp_file, &file).unwrap();
+                println!("imported {} genomes", genomes.len());
+            }
+            Command::Hof { file, pool, export } => {
+                let server = load_or_exit(&file);
+                println!("generation pool score nodes links");
+                for champion in &server.hall_of_fame.champions {
+                    if pool.map_or(false, |pool| pool != champion.pool) {
+                        continue;
+                    }
+                    let links: usize = champion
+                        .genome
+                        .nodes
+                        .values()
+                        .map(|node| node.inputs.len())
+                        .sum();
+                    println!(
+                        "{} {} {:.3} {} {}",
+                        champion.generation,
+                        champion.pool,
+                        champion.score,
+                        champion.genome.nodes.len(),
+                        links
+                    );
+                    if let Some(dir) = &export {
+                        let path = dir.join(format!(
+                            "gen-{}.pool-{}.json",
+                            champion.generation, champion.pool
+                        ));
+                        std::fs::write(path, champion.genome.to_json()).unwrap();
+                    }
+                }
+            }
+            Command::Report {
+                file,
+                pool,
+                top,
+                json,
+            } => {
+                let server = load_or_exit(&file);
+                let reports: Vec<_> = report::reports(&server, top)
+                    .into_iter()
+                    .filter(|r| pool.map_or(true, |pool| pool == r.pool))
+                    .collect();
+                if json {
+                    println!("{}", serde_json::to_string_pretty(&reports).unwrap());
+                } else {
+                    println!("generation {}", server.generation);
+                    reports.iter().for_each(|r| r.print());
+                }
+            }
+            Command::Diff { old, new, pool } => {
+                report::print_diff(&load_or_exit(&old), &load_or_exit(&new), pool);
+            }
+            Command::HofEval {
+                file,
+                pool,
+                every,
+                copies,
+            } => {
+                let server = load_or_exit(&file);
+                println!("generation pool archived today");
+                for champion in &server.hall_of_fame.champions {
+                    if pool.map_or(false, |pool| pool != champion.pool)
+                        || champion.generation % every.max(1) != 0
+                    {
+                        continue;
+                    }
+                    let today = server.evaluate(&champion.genome, champion.pool, copies);
+                    println!(
+                        "{} {} {:.3} {:.3}",
+                        champion.generation, champion.pool, champion.score, today
+                    );
+                }
+            }
+            Command::ArmsRace { file, samples } => {
+                let server = load_or_exit(&file);
+                let scenario = server.sim.world.scenario.unwrap_or_else(|| {
+                    eprintln!("{} has no co-evolution scenario", file.to_string_lossy());
+                    std::process::exit(1);
+                });
+                for race in coevolution::arms_race(&server, &scenario, samples) {
+                    race.print();
+                }
+            }
+        }
+    }
+}
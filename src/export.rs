@@ -0,0 +1,254 @@
+use std::{collections::HashMap, fmt::Write, str::FromStr};
+
+use crate::net::{NetGenome, NeuralLink, NeuralSource, NeuralTarget};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    Dot,
+    Mermaid,
+    /// The editable genome itself, as read back by `import` and `--seed`.
+    Json,
+}
+
+impl FromStr for Format {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "dot" => Ok(Format::Dot),
+            "mermaid" => Ok(Format::Mermaid),
+            "json" => Ok(Format::Json),
+            _ => Err(format!(
+                "unknown format {:?}, expected dot, mermaid or json",
+                s
+            )),
+        }
+    }
+}
+
+impl Format {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Format::Dot => "dot",
+            Format::Mermaid => "mmd",
+            Format::Json => "json",
+        }
+    }
+    pub fn render(&self, genome: &NetGenome) -> String {
+        match self {
+            Format::Dot => dot(genome),
+            Format::Mermaid => mermaid(genome),
+            Format::Json => genome.to_json(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Class {
+    Sensor,
+    Hidden,
+    Action,
+}
+
+struct Vertex {
+    id: String,
+    label: String,
+    /// Activation function of hidden and action neurons.
+    detail: Option<String>,
+    class: Class,
+}
+
+struct Edge<'a> {
+    from: String,
+    to: String,
+    link: &'a NeuralLink,
+}
+
+/// Flattens the genome into vertices and edges with identifiers that are
+/// valid in both output formats, sorted so exports are stable.
+fn graph(genome: &NetGenome) -> (Vec<Vertex>, Vec<Edge<'_>>) {
+    let mut vertices: HashMap<String, Vertex> = HashMap::new();
+    let mut edges = vec![];
+
+    let mut target_vertex = |target: &NeuralTarget| {
+        let (id, label, class) = match target {
+            NeuralTarget::Hidden(hid) => (format!("h{}", hid.0), hid.to_string(), Class::Hidden),
+            NeuralTarget::Action(action) => (
+                format!("{:?}", action),
+                format!("{:?}", action),
+                Class::Action,
+            ),
+        };
+        vertices.entry(id.clone()).or_insert(Vertex {
+            id: id.clone(),
+            label,
+            detail: genome
+                .nodes
+                .get(target)
+                .map(|node| format!("{:?}", node.params.activation)),
+            class,
+        });
+        id
+    };
+    let mut sensor_ids = vec![];
+    for (target, node) in &genome.nodes {
+        let to = target_vertex(target);
+        for (source, link) in &node.inputs {
+            let from = match source {
+                NeuralSource::Hidden(hid) => target_vertex(&NeuralTarget::Hidden(*hid)),
+                NeuralSource::Sensor(sensor) => {
+                    let label = format!("{:?}", sensor);
+                    sensor_ids.push(label.clone());
+                    label
+                }
+            };
+            edges.push(Edge {
+                from,
+                to: to.clone(),
+                link,
+            });
+        }
+    }
+    sensor_ids.sort();
+    sensor_ids.dedup();
+    for (i, label) in sensor_ids.iter().enumerate() {
+        vertices.insert(
+            format!("s{}", i),
+            Vertex {
+                id: format!("s{}", i),
+                label: label.clone(),
+                detail: None,
+                class: Class::Sensor,
+            },
+        );
+    }
+    for edge in &mut edges {
+        if let Ok(i) = sensor_ids.binary_search(&edge.from) {
+            edge.from = format!("s{}", i);
+        }
+    }
+
+    let mut vertices: Vec<_> = vertices.into_values().collect();
+    vertices.sort_by(|a, b| a.id.cmp(&b.id));
+    edges.sort_by(|a, b| (&a.from, &a.to).cmp(&(&b.from, &b.to)));
+    (vertices, edges)
+}
+
+fn edge_color(link: &NeuralLink) -> &'static str {
+    if link.weight >= 0.0 {
+        "#2166ac"
+    } else {
+        "#b2182b"
+    }
+}
+
+fn edge_width(link: &NeuralLink) -> f32 {
+    0.5 + link.weight.abs()
+}
+
+pub fn dot(genome: &NetGenome) -> String {
+    let (vertices, edges) = graph(genome);
+    let mut out = String::new();
+    writeln!(out, "digraph genome {{").unwrap();
+    writeln!(out, "    rankdir=LR;").unwrap();
+    writeln!(out, "    node [style=filled, fontname=\"monospace\"];").unwrap();
+    for vertex in &vertices {
+        let (shape, fill) = match vertex.class {
+            Class::Sensor => ("box", "#deebf7"),
+            Class::Hidden => ("circle", "#f0f0f0"),
+            Class::Action => ("doublecircle", "#e5f5e0"),
+        };
+        let label = match &vertex.detail {
+            Some(detail) => format!("{}\\n{}", vertex.label, detail),
+            None => vertex.label.clone(),
+        };
+        writeln!(
+            out,
+            "    \"{}\" [label=\"{}\", shape={}, fillcolor=\"{}\"];",
+            vertex.id,
+            label.replace('"', "\\\""),
+            shape,
+            fill
+        )
+        .unwrap();
+    }
+    for edge in &edges {
+        writeln!(
+            out,
+            "    \"{}\" -> \"{}\" [label=\"{:.2}{}\", penwidth={:.2}, color=\"{}\"{}];",
+            edge.from,
+            edge.to,
+            edge.link.weight,
+            if edge.link.plastic.is_some() { "~" } else { "" },
+            edge_width(edge.link),
+            edge_color(edge.link),
+            if edge.link.inverse {
+                ", style=dashed"
+            } else {
+                ""
+            }
+        )
+        .unwrap();
+    }
+    writeln!(out, "}}").unwrap();
+    out
+}
+
+pub fn mermaid(genome: &NetGenome) -> String {
+    let (vertices, edges) = graph(genome);
+    let mut out = String::new();
+    writeln!(out, "graph LR").unwrap();
+    for vertex in &vertices {
+        let label = match &vertex.detail {
+            Some(detail) => format!("{}<br/>{}", vertex.label, detail),
+            None => vertex.label.clone(),
+        }
+        .replace('"', "#quot;");
+        match vertex.class {
+            Class::Sensor => writeln!(out, "    {}[\"{}\"]", vertex.id, label),
+            Class::Hidden => writeln!(out, "    {}((\"{}\"))", vertex.id, label),
+            Class::Action => writeln!(out, "    {}{{{{\"{}\"}}}}", vertex.id, label),
+        }
+        .unwrap();
+    }
+    for edge in &edges {
+        let arrow = if edge.link.inverse { "-.->" } else { "-->" };
+        writeln!(
+            out,
+            "    {} {}|{:.2}{}| {}",
+            edge.from,
+            arrow,
+            edge.link.weight,
+            if edge.link.plastic.is_some() { "~" } else { "" },
+            edge.to
+        )
+        .unwrap();
+    }
+    writeln!(out, "    classDef sensor fill:#deebf7").unwrap();
+    writeln!(out, "    classDef hidden fill:#f0f0f0").unwrap();
+    writeln!(out, "    classDef action fill:#e5f5e0").unwrap();
+    for (class, name) in [
+        (Class::Sensor, "sensor"),
+        (Class::Hidden, "hidden"),
+        (Class::Action, "action"),
+    ] {
+        let ids: Vec<_> = vertices
+            .iter()
+            .filter(|v| v.class == class)
+            .map(|v| v.id.as_str())
+            .collect();
+        if !ids.is_empty() {
+            writeln!(out, "    class {} {}", ids.join(","), name).unwrap();
+        }
+    }
+    for (i, edge) in edges.iter().enumerate() {
+        writeln!(
+            out,
+            "    linkStyle {} stroke:{},stroke-width:{:.1}px",
+            i,
+            edge_color(edge.link),
+            edge_width(edge.link)
+        )
+        .unwrap();
+    }
+    out
+}
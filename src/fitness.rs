@@ -0,0 +1,187 @@
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{replicant::Replicant, simulation::Simulation};
+
+/// Grades how well a replicant did over the round that just ended.
+pub trait Fitness {
+    fn score(&self, rep: &Replicant, sim: &Simulation) -> f32;
+}
+
+/// Survival as sampled by the simulation's `Evaluation` schedule, which with
+/// `Evaluation::FractionAlive` is the fraction of the round spent alive.
+pub struct Survival;
+
+impl Fitness for Survival {
+    fn score(&self, rep: &Replicant, sim: &Simulation) -> f32 {
+        sim.survival(rep)
+    }
+}
+
+/// Distance travelled, as steps taken per tick.
+pub struct Distance;
+
+impl Fitness for Distance {
+    fn score(&self, rep: &Replicant, _sim: &Simulation) -> f32 {
+        rep.moves as f32 / rep.time.max(1) as f32
+    }
+}
+
+/// Distinct cells visited per tick.
+pub struct Explored;
+
+impl Fitness for Explored {
+    fn score(&self, rep: &Replicant, _sim: &Simulation) -> f32 {
+        rep.visited.len() as f32 / (rep.time + 1) as f32
+    }
+}
+
+/// Fraction of the round spent next to a member of the opposing pool of a
+/// co-evolution scenario.
+pub struct Contact;
+
+impl Fitness for Contact {
+    fn score(&self, rep: &Replicant, _sim: &Simulation) -> f32 {
+        rep.contacts as f32 / rep.time.max(1) as f32
+    }
+}
+
+/// Fraction of the round spent free: before being caught, and away from the
+/// opposing pool.
+pub struct Evasion;
+
+impl Fitness for Evasion {
+    fn score(&self, rep: &Replicant, _sim: &Simulation) -> f32 {
+        let free = rep.caught.unwrap_or(rep.time).saturating_sub(rep.contacts);
+        free as f32 / rep.time.max(1) as f32
+    }
+}
+
+/// Prey caught, as a share of the prey of the scenario.
+pub struct Captures;
+
+impl Fitness for Captures {
+    fn score(&self, rep: &Replicant, sim: &Simulation) -> f32 {
+        let prey = match sim.world.scenario.and_then(|s| s.hunt()) {
+            Some((_, prey)) => prey,
+            None => return 0.0,
+        };
+        let count = sim
+            .replicants
+            .iter()
+            .filter(|other| other.net.pool() == prey)
+            .count();
+        rep.captures as f32 / count.max(1) as f32
+    }
+}
+
+/// Serialisable choice of fitness function, selected per pool. There is no
+/// food in the world, so it is not scored; kills are `Captures`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum FitnessFn {
+    Survival,
+    Distance,
+    Explored,
+    Contact,
+    Evasion,
+    Captures,
+    /// Weighted sum of other fitness functions.
+    Weighted(Vec<(f32, FitnessFn)>),
+}
+
+impl Default for FitnessFn {
+    fn default() -> Self {
+        FitnessFn::Survival
+    }
+}
+
+impl Fitness for FitnessFn {
+    fn score(&self, rep: &Replicant, sim: &Simulation) -> f32 {
+        match self {
+            FitnessFn::Survival => Survival.score(rep, sim),
+            FitnessFn::Distance => Distance.score(rep, sim),
+            FitnessFn::Explored => Explored.score(rep, sim),
+            FitnessFn::Contact => Contact.score(rep, sim),
+            FitnessFn::Evasion => Evasion.score(rep, sim),
+            FitnessFn::Captures => Captures.score(rep, sim),
+            FitnessFn::Weighted(terms) => terms
+                .iter()
+                .map(|(weight, f)| weight * f.score(rep, sim))
+                .sum(),
+        }
+    }
+}
+
+/// Parses `survival`, `distance`, `explored`, `contact`, `evasion`,
+/// `captures`, or a weighted sum of them such as `0.7*survival+0.3*explored`.
+impl FromStr for FitnessFn {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.contains('+') || s.contains('*') {
+            let terms = s
+                .split('+')
+                .map(|term| match term.split_once('*') {
+                    Some((weight, f)) => Ok((
+                        weight
+                            .trim()
+                            .parse::<f32>()
+                            .map_err(|e| format!("{:?}: {}", weight, e))?,
+                        f.parse()?,
+                    )),
+                    None => Ok((1.0, term.parse()?)),
+                })
+                .collect::<Result<_, String>>()?;
+            return Ok(FitnessFn::Weighted(terms));
+        }
+        match s.trim() {
+            "survival" => Ok(FitnessFn::Survival),
+            "distance" => Ok(FitnessFn::Distance),
+            "explored" => Ok(FitnessFn::Explored),
+            "contact" => Ok(FitnessFn::Contact),
+            "evasion" => Ok(FitnessFn::Evasion),
+            "captures" => Ok(FitnessFn::Captures),
+            other => Err(format!("unknown fitness function {:?}", other)),
+        }
+    }
+}
+
+/// A criterion of multi-objective selection. Every objective is maximised,
+/// costs are negated.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Objective {
+    Fitness(FitnessFn),
+    /// Fewer moves per tick, as an energy cost.
+    Moves,
+    /// Fewer links.
+    Size,
+}
+
+impl Objective {
+    pub fn value(&self, rep: &Replicant, sim: &Simulation) -> f32 {
+        match self {
+            Objective::Fitness(f) => f.score(rep, sim),
+            Objective::Moves => -(rep.moves as f32) / (rep.time + 1) as f32,
+            Objective::Size => {
+                -(rep
+                    .net
+                    .nodes
+                    .values()
+                    .map(|node| node.inputs.len())
+                    .sum::<usize>() as f32)
+            }
+        }
+    }
+}
+
+/// Parses `moves`, `size` or a fitness function.
+impl FromStr for Objective {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "moves" => Ok(Objective::Moves),
+            "size" => Ok(Objective::Size),
+            f => Ok(Objective::Fitness(f.parse()?)),
+        }
+    }
+}
@@ -1,6 +1,8 @@
 use rand::{distributions::Standard, prelude::Distribution, random, Rng};
 use serde::{Deserialize, Serialize};
 
+use crate::net;
+
 #[derive(Clone, Copy, Debug, PartialEq, Hash, Eq, Serialize, Deserialize)]
 pub enum NeighbourType {
     Any,
@@ -16,7 +18,7 @@ impl Distribution<NeighbourType> for Standard {
             NeighbourType::Any,
             NeighbourType::Friend,
             NeighbourType::Enemy,
-            NeighbourType::Pool(random::<usize>() % 3),
+            NeighbourType::Pool(random::<usize>() % net::pools()),
         ];
         let i = random::<usize>() % actions.len();
         *actions.get(i).unwrap()
@@ -0,0 +1,166 @@
+use std::str::FromStr;
+
+use rand::random;
+use rayon::prelude::*;
+
+use crate::{genome::HasGenome, net::NetGenome, replicant::Replicant, server::Server};
+
+/// Which islands send migrants to which.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Topology {
+    /// Each island to the next one.
+    Ring,
+    /// Each island to all the others.
+    Full,
+    /// Each island to another one picked at every migration.
+    Random,
+}
+
+/// Parses `ring`, `full` or `random`.
+impl FromStr for Topology {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ring" => Ok(Topology::Ring),
+            "full" => Ok(Topology::Full),
+            "random" => Ok(Topology::Random),
+            _ => Err(format!("unknown topology {:?}", s)),
+        }
+    }
+}
+
+impl Topology {
+    pub fn neighbours(&self, island: usize, islands: usize) -> Vec<usize> {
+        if islands < 2 {
+            return vec![];
+        }
+        match self {
+            Topology::Ring => vec![(island + 1) % islands],
+            Topology::Full => (0..islands).filter(|i| *i != island).collect(),
+            Topology::Random => {
+                vec![(island + 1 + random::<usize>() % (islands - 1)) % islands]
+            }
+        }
+    }
+}
+
+/// What migrates between islands.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Migration {
+    /// Random members of the next generation, replacing random members of
+    /// the neighbour's.
+    Genomes,
+    /// The genome each pool assembles, injected into the neighbour's gene
+    /// pools.
+    Alleles,
+}
+
+/// Independent servers evolving side by side, exchanging genomes every
+/// `interval` generations. Each island saves itself like a single server.
+pub struct Archipelago {
+    pub islands: Vec<Server>,
+    pub topology: Topology,
+    pub migration: Migration,
+    /// Generations between migrations, 0 for none.
+    pub interval: usize,
+    /// Genomes each island sends to each neighbour.
+    pub migrants: usize,
+    /// Generations run since the archipelago started.
+    pub generations: usize,
+}
+
+impl Archipelago {
+    pub fn new(islands: Vec<Server>) -> Self {
+        Self {
+            islands,
+            topology: Topology::Ring,
+            migration: Migration::Genomes,
+            interval: 10,
+            migrants: 10,
+            generations: 0,
+        }
+    }
+
+    /// The pool count, hidden neuron IDs and size limits are set for the
+    /// whole process, so every island has to use the same ones.
+    pub fn check_shared(&self) -> Result<(), String> {
+        let first = match self.islands.first() {
+            Some(first) => first,
+            None => return Ok(()),
+        };
+        for (i, island) in self.islands.iter().enumerate().skip(1) {
+            if island.pools.len() != first.pools.len() {
+                return Err(format!(
+                    "island {} has {} pools, island 0 {}",
+                    i,
+                    island.pools.len(),
+                    first.pools.len()
+                ));
+            }
+            if island.hidden_ids != first.hidden_ids {
+                return Err(format!(
+                    "island {} numbers hidden neurons {:?}, island 0 {:?}",
+                    i, island.hidden_ids, first.hidden_ids
+                ));
+            }
+            if island.limits != first.limits {
+                return Err(format!(
+                    "island {} has limits {:?}, island 0 {:?}",
+                    i, island.limits, first.limits
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn run(&mut self) {
+        loop {
+            self.islands
+                .par_iter_mut()
+                .for_each(|island| island.step_generation());
+            self.generations += 1;
+            if self.interval > 0 && self.generations % self.interval == 0 {
+                self.migrate();
+            }
+        }
+    }
+
+    pub fn migrate(&mut self) {
+        let n = self.islands.len();
+        let mut arrivals: Vec<Vec<(usize, NetGenome)>> = vec![vec![]; n];
+        for (i, island) in self.islands.iter().enumerate() {
+            for neighbour in self.topology.neighbours(i, n) {
+                match self.migration {
+                    Migration::Genomes => {
+                        let replicants = &island.sim.replicants;
+                        for _ in 0..self.migrants.min(replicants.len()) {
+                            let rep = &replicants[random::<usize>() % replicants.len()];
+                            let genome = rep.to_genome();
+                            arrivals[neighbour].push((genome.pool(), genome));
+                        }
+                    }
+                    Migration::Alleles => arrivals[neighbour].extend(island.champions()),
+                }
+            }
+        }
+        for (island, arrivals) in self.islands.iter_mut().zip(arrivals) {
+            eprintln!(
+                "island {}: {} migrants",
+                island.island.unwrap_or_default(),
+                arrivals.len()
+            );
+            for (pool, genome) in arrivals {
+                match self.migration {
+                    Migration::Genomes => {
+                        let len = island.sim.replicants.len();
+                        if len > 0 {
+                            island.sim.replicants[random::<usize>() % len] =
+                                Replicant::from_genome(&genome);
+                        }
+                    }
+                    Migration::Alleles => island.inject(&genome, pool),
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,306 @@
+// Save format used while hidden neurons were identified by "h-N" strings,
+// kept so that runs started with those versions can still be resumed.
+
+use std::{collections::HashMap, path::PathBuf};
+
+use serde::Deserialize;
+
+use crate::{
+    actions::Action,
+    genome::HasGenome,
+    input::Sensor,
+    net::{NetGenome, NeuralLink, NeuralNode, NeuralSource, NeuralTarget, NeuronID},
+    pool::{Allele, GenePool, Score},
+    replicant::Replicant,
+    server::Server,
+    simulation::CellMapper,
+    world::World,
+};
+
+#[derive(Deserialize)]
+#[cfg_attr(test, derive(serde::Serialize))]
+struct LegacyServer {
+    auto_save: Option<PathBuf>,
+    generation: usize,
+    _time: usize,
+    sim: LegacySimulation,
+    gene_pools: HashMap<usize, LegacyGenePool>,
+    pop_size: usize,
+    prev_survival: [usize; 3],
+}
+
+#[derive(Deserialize)]
+#[cfg_attr(test, derive(serde::Serialize))]
+struct LegacySimulation {
+    world: LegacyWorld,
+    replicants: Vec<LegacyReplicant>,
+    _mapper: CellMapper,
+}
+
+#[derive(Deserialize)]
+#[cfg_attr(test, derive(serde::Serialize))]
+struct LegacyWorld {
+    width: i32,
+    height: i32,
+}
+
+#[derive(Deserialize)]
+#[cfg_attr(test, derive(serde::Serialize))]
+struct LegacyReplicant {
+    _pos: (i32, i32),
+    net: LegacyNet,
+    _time: usize,
+    _moves: usize,
+}
+
+#[derive(Deserialize)]
+#[cfg_attr(test, derive(serde::Serialize))]
+struct LegacyNet {
+    nodes: HashMap<LegacyTarget, LegacyNode>,
+    _sensors: HashMap<Sensor, f32>,
+    // Neurons only had a charge back then.
+    _state: HashMap<LegacyTarget, f32>,
+    color: [f32; 3],
+}
+
+#[derive(Deserialize)]
+#[cfg_attr(test, derive(serde::Serialize))]
+struct LegacyNode {
+    inputs: HashMap<LegacySource, LegacyLink>,
+}
+
+#[derive(Deserialize)]
+#[cfg_attr(test, derive(serde::Serialize))]
+struct LegacyLink {
+    inverse: bool,
+    weight: f32,
+}
+
+#[derive(Deserialize, PartialEq, Eq, Hash)]
+#[cfg_attr(test, derive(serde::Serialize))]
+enum LegacySource {
+    Sensor(Sensor),
+    Hidden(String),
+}
+
+#[derive(Deserialize, PartialEq, Eq, Hash)]
+#[cfg_attr(test, derive(serde::Serialize))]
+enum LegacyTarget {
+    Hidden(String),
+    Action(Action),
+}
+
+#[derive(Deserialize)]
+#[cfg_attr(test, derive(serde::Serialize))]
+struct LegacyGenePool {
+    genes: HashMap<LegacyTarget, HashMap<u64, (LegacyNode, LegacyScore)>>,
+}
+
+#[derive(Deserialize)]
+#[cfg_attr(test, derive(serde::Serialize))]
+struct LegacyScore {
+    _avg: f32,
+    tot: f32,
+    count: usize,
+}
+
+#[derive(Default)]
+struct Converter {
+    ids: HashMap<String, NeuronID>,
+}
+
+impl Converter {
+    fn id(&mut self, id: &str) -> NeuronID {
+        if let Some(id) = self.ids.get(id) {
+            return *id;
+        }
+        // Anything that does not look like "h-N" gets an ID past the old
+        // ten-neuron namespace.
+        let new = id
+            .strip_prefix("h-")
+            .and_then(|n| n.parse().ok())
+            .map(NeuronID)
+            .unwrap_or(NeuronID(1000 + self.ids.len() as u32));
+        self.ids.insert(id.to_string(), new);
+        new
+    }
+    fn target(&mut self, target: &LegacyTarget) -> NeuralTarget {
+        match target {
+            LegacyTarget::Hidden(id) => NeuralTarget::Hidden(self.id(id)),
+            LegacyTarget::Action(action) => NeuralTarget::Action(*action),
+        }
+    }
+    fn node(&mut self, node: &LegacyNode) -> NeuralNode {
+        let mut new = NeuralNode::new();
+        for (source, link) in &node.inputs {
+            let source = match source {
+                LegacySource::Sensor(sensor) => NeuralSource::Sensor(*sensor),
+                LegacySource::Hidden(id) => NeuralSource::Hidden(self.id(id)),
+            };
+            let mut new_link = NeuralLink::new();
+            new_link.inverse = link.inverse;
+            new_link.weight = link.weight;
+            new_link.plastic = None;
+            new.inputs.insert(source, new_link);
+        }
+        new
+    }
+    fn genome(&mut self, net: &LegacyNet) -> NetGenome {
+        let mut genome = NetGenome {
+            pool: 0,
+            color: net.color.to_vec(),
+            nodes: net
+                .nodes
+                .iter()
+                .map(|(target, node)| (self.target(target), self.node(node)))
+                .collect(),
+        };
+        genome.pool = genome.color_pool();
+        genome.repair();
+        genome
+    }
+}
+
+/// Converts a save written before hidden neurons had typed IDs. The round in
+/// progress is restarted, the gene pools and the population are kept.
+pub fn migrate(content: &[u8]) -> Option<Server> {
+    let legacy: LegacyServer = bincode::deserialize(content).ok()?;
+    let mut converter = Converter::default();
+
+    let mut server = Server {
+        auto_save: legacy.auto_save,
+        generation: legacy.generation,
+        time: 0,
+        pop_size: legacy.pop_size,
+        prev_survival: legacy.prev_survival.to_vec(),
+        ..Default::default()
+    };
+    server.sim.world = World {
+        width: legacy.sim.world.width,
+        height: legacy.sim.world.height,
+        ..Default::default()
+    };
+    server.sim.replicants = legacy
+        .sim
+        .replicants
+        .iter()
+        .map(|rep| Replicant::from_genome(&converter.genome(&rep.net)))
+        .collect();
+    for (i, pool) in &legacy.gene_pools {
+        let mut new = GenePool::new();
+        for (gene, alleles) in &pool.genes {
+            let gene = converter.target(gene);
+            for (node, score) in alleles.values() {
+                let node = converter.node(node);
+                new.genes.entry(gene.clone()).or_default().insert(
+                    node.get_allele_id(),
+                    (node, Score::new(score.tot, score.count)),
+                );
+            }
+        }
+        server.gene_pools.insert(*i, new);
+    }
+    Some(server)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::net::NeuronID;
+
+    fn node(inputs: Vec<(LegacySource, f32)>) -> LegacyNode {
+        LegacyNode {
+            inputs: inputs
+                .into_iter()
+                .map(|(source, weight)| {
+                    let link = LegacyLink {
+                        inverse: false,
+                        weight,
+                    };
+                    (source, link)
+                })
+                .collect(),
+        }
+    }
+
+    fn net() -> LegacyNet {
+        let mut nodes = HashMap::new();
+        nodes.insert(
+            LegacyTarget::Hidden("h-2".to_string()),
+            node(vec![(LegacySource::Sensor(Sensor::Bias(1)), 0.5)]),
+        );
+        nodes.insert(
+            LegacyTarget::Action(Action::IncX),
+            node(vec![(LegacySource::Hidden("h-2".to_string()), -1.0)]),
+        );
+        LegacyNet {
+            nodes,
+            _sensors: HashMap::new(),
+            _state: HashMap::new(),
+            color: [0.0, 1.0, 0.0],
+        }
+    }
+
+    #[test]
+    fn migrates_a_baseline_save() {
+        let mut genes = HashMap::new();
+        let mut alleles = HashMap::new();
+        let score = LegacyScore {
+            _avg: 0.5,
+            tot: 2.0,
+            count: 4,
+        };
+        let allele = node(vec![(LegacySource::Sensor(Sensor::Alive), 2.0)]);
+        alleles.insert(1, (allele, score));
+        genes.insert(LegacyTarget::Hidden("h-2".to_string()), alleles);
+        let legacy = LegacyServer {
+            auto_save: None,
+            generation: 7,
+            _time: 120,
+            sim: LegacySimulation {
+                world: LegacyWorld {
+                    width: 40,
+                    height: 30,
+                },
+                replicants: vec![LegacyReplicant {
+                    _pos: (3, 4),
+                    net: net(),
+                    _time: 120,
+                    _moves: 9,
+                }],
+                _mapper: CellMapper::default(),
+            },
+            gene_pools: [(1, LegacyGenePool { genes })].into_iter().collect(),
+            pop_size: 1,
+            prev_survival: [1, 2, 3],
+        };
+        let content = bincode::serialize(&legacy).unwrap();
+
+        let server = migrate(&content).unwrap();
+        assert_eq!(server.generation, 7);
+        assert_eq!(server.time, 0);
+        assert_eq!(server.prev_survival, vec![1, 2, 3]);
+        assert_eq!((server.sim.world.width, server.sim.world.height), (40, 30));
+
+        let genome = server.sim.replicants[0].to_genome();
+        assert_eq!(genome.pool(), 1);
+        let hidden = NeuralTarget::Hidden(NeuronID(2));
+        assert!(genome.nodes.contains_key(&hidden));
+        assert!(genome.nodes[&NeuralTarget::Action(Action::IncX)]
+            .inputs
+            .contains_key(&NeuralSource::Hidden(NeuronID(2))));
+
+        let alleles = &server.gene_pools[&1].genes[&hidden];
+        assert_eq!(alleles.len(), 1);
+        let (_, score) = alleles.values().next().unwrap();
+        assert_eq!(score.count(), 4);
+        assert_eq!(score.avg(), 0.5);
+        assert_eq!(score.std(), 1.0);
+
+        // And the result saves and loads in the current format.
+        let saved = bincode::serialize(&server).unwrap();
+        let loaded: Server = bincode::deserialize(&saved).unwrap();
+        assert_eq!(loaded.generation, 7);
+        assert_eq!(loaded.gene_pools[&1].genes[&hidden].len(), 1);
+    }
+}
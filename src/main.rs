@@ -1,19 +1,44 @@
-use std::path::PathBuf;
+use std::{path::PathBuf, str::FromStr};
 
+use coevolution::Scenario;
+use commands::Command;
+use fitness::FitnessFn;
+use island::{Archipelago, Migration, Topology};
+use simulation::{Evaluation, RoundLength};
+use net::{HiddenIds, Inheritance};
+use pool::{Decay, GenePool, PoolConfig, PruneConfig, Selection};
 use render::Render;
+use schedule::{Change, Schedule, Trigger};
 use server::Server;
+use strategy::{PoolMigration, Strategy};
 use structopt::StructOpt;
+use world::SurvivalRule;
 
 mod actions;
+mod analysis;
+mod archive;
+mod behaviour;
+mod coevolution;
+mod commands;
+mod export;
+mod fitness;
 mod genome;
 mod input;
+mod island;
+mod legacy;
 mod net;
+mod nsga;
+mod parsimony;
 mod pool;
 mod render;
 mod replicant;
+mod report;
 mod rng;
+mod schedule;
 mod server;
 mod simulation;
+mod species;
+mod strategy;
 mod world;
 
 /// A fictional versioning CLI
@@ -23,22 +48,366 @@ mod world;
 struct Cli {
     #[structopt(short, long)]
     render: bool,
+    /// Write weights learned by plastic links back into the offspring
+    /// genomes. Gene pools keep telling alleles apart by the weights they
+    /// were bred with
+    #[structopt(long)]
+    lamarckian: bool,
+    /// Evolve with NEAT speciation instead of the colour pools, same as
+    /// --strategy neat
+    #[structopt(long)]
+    neat: bool,
+    /// How generations are bred: pools, neat, generational[:tournament[:size]
+    /// |:roulette][:nearest], mu+lambda:<mu>, novelty[:<descriptor>],
+    /// map-elites[:<descriptor>[:<bins>]], nsga2:<objective>,<objective>... or
+    /// elitism:<count>:<strategy>, with descriptors position, visited or
+    /// actions and objectives moves, size or a fitness function
+    #[structopt(long)]
+    strategy: Option<String>,
+    /// Number of hidden neuron IDs mutations draw from, 0 for unbounded
+    #[structopt(long)]
+    hidden_namespace: Option<u32>,
+    /// Probability of replacing a child with its simplified genome
+    #[structopt(long)]
+    simplify_rate: Option<f32>,
+    /// Directory of genomes to breed the first generation from
+    #[structopt(long)]
+    seed: Option<PathBuf>,
+    /// Fitness function of a pool as <pool>:<function>, e.g.
+    /// 1:0.5*survival+0.5*explored (functions: survival, distance, explored)
+    #[structopt(long, number_of_values = 1)]
+    fitness: Vec<String>,
+    /// When survival is sampled: final, fraction or checkpoints:<n>
+    #[structopt(long)]
+    evaluation: Option<Evaluation>,
+    /// Ticks a round lasts: fixed:<ticks>, uniform:<min>,<max> or
+    /// geometric:<min>,<mean>, drawn again every round
+    #[structopt(long)]
+    round_length: Option<RoundLength>,
+    /// Rounds each generation is evaluated over, fitness being averaged
+    #[structopt(long)]
+    episodes: Option<usize>,
+    /// Allele selection of a pool as <pool>:<strategy>, with strategies
+    /// greedy, ucb1[:c], thompson, softmax[:temperature] or tournament[:size]
+    #[structopt(long, number_of_values = 1)]
+    selection: Vec<String>,
+    /// How old evaluations of a pool lose weight, as <pool>:<decay> with
+    /// decays lifetime, ema[:factor], window[:generations] or age[:half life]
+    #[structopt(long, number_of_values = 1)]
+    decay: Vec<String>,
+    /// Prune the gene pools every generation, optionally with limits as
+    /// --prune=<min evaluations>,<alleles per gene>,<alleles per pool>
+    #[structopt(long, require_equals = true)]
+    prune: Option<Option<PruneConfig>>,
+    /// Fitness lost per node of a network
+    #[structopt(long)]
+    node_cost: Option<f32>,
+    /// Fitness lost per link of a network
+    #[structopt(long)]
+    link_cost: Option<f32>,
+    /// Prefer the smaller of two equally fit genomes
+    #[structopt(long)]
+    size_tie_break: bool,
+    /// Most nodes a genome may have
+    #[structopt(long)]
+    max_nodes: Option<usize>,
+    /// Most links a genome may have
+    #[structopt(long)]
+    max_links: Option<usize>,
+    /// Most hidden neurons a genome may have
+    #[structopt(long)]
+    max_hidden: Option<usize>,
+    /// Where replicants survive: zone[:<x>], region:<x0>,<x1>,<y0>,<y1> or
+    /// crowd
+    #[structopt(long)]
+    survival: Option<SurvivalRule>,
+    /// Evolve this many islands in parallel, saved next to the save file
+    #[structopt(long)]
+    islands: Option<usize>,
+    /// Islands migrants go to: ring, full or random
+    #[structopt(long, default_value = "ring")]
+    topology: Topology,
+    /// Generations between migrations, 0 for none
+    #[structopt(long, default_value = "10")]
+    migration_interval: usize,
+    /// Genomes sent to each neighbouring island
+    #[structopt(long, default_value = "10")]
+    migrants: usize,
+    /// Migrate the genome each pool assembles into the gene pools of the
+    /// neighbours instead of members of the population
+    #[structopt(long)]
+    migrate_alleles: bool,
+    /// World of an island as <island>:<change>, with the changes of
+    /// --schedule made from generation 0
+    #[structopt(long, number_of_values = 1)]
+    island_world: Vec<String>,
+    /// Number of colour pools. Members of the pools dropped by a smaller
+    /// count move to the remaining ones
+    #[structopt(long)]
+    pools: Option<usize>,
+    /// Configuration of a pool as <pool>:<name>[:<#rrggbb>[:<mutation
+    /// rate>]], empty fields keeping their value
+    #[structopt(long, number_of_values = 1)]
+    pool: Vec<String>,
+    /// Co-evolution scenario: predator-prey[:<predator>,<prey>] or
+    /// host-parasite[:<host>,<parasite>], setting the fitness of both pools.
+    /// Predators catch the prey on neighbouring cells, parasites score by the
+    /// ticks spent next to hosts
+    #[structopt(long)]
+    scenario: Option<Scenario>,
+    /// Generations between cross-evaluations of the scenario's pools against
+    /// past opponents, written to the metrics
+    #[structopt(long)]
+    arms_race_interval: Option<usize>,
+    /// Change to the world as <trigger>=<change>, applied in the given order.
+    /// Triggers are at:<generation> or when:<survival share>, changes
+    /// size:<width>,<height>, obstacles:<count>, wall:<x>[,<gap>],
+    /// clear-obstacles or a survival rule. Changes leaving fewer free cells
+    /// than replicants are skipped
+    #[structopt(long, number_of_values = 1)]
+    schedule: Vec<String>,
+    /// How children change pool: none, random:<rate> or colour, where the
+    /// colour mutates and decides the pool
+    #[structopt(long)]
+    pool_migration: Option<PoolMigration>,
     file: Option<PathBuf>,
+    #[structopt(subcommand)]
+    cmd: Option<Command>,
+}
+
+/// Parses a `<pool>:<value>` option, exiting on errors.
+fn per_pool<T: FromStr<Err = String>>(option: &str, spec: &str) -> (usize, T) {
+    let parsed = spec
+        .split_once(':')
+        .ok_or_else(|| "expected <pool>:<value>".to_string())
+        .and_then(|(pool, value)| {
+            let pool = pool.parse::<usize>().map_err(|e| e.to_string())?;
+            Ok((pool, value.parse::<T>()?))
+        });
+    parsed.unwrap_or_else(|e| {
+        eprintln!("{} {}: {}", option, spec, e);
+        std::process::exit(1);
+    })
+}
+
+/// Applies the command line options to `server`, whose world starts with
+/// `world` changes.
+fn configure(server: &mut Server, args: &Cli, world: &[Change]) {
+    if args.lamarckian {
+        server.inheritance = Inheritance::Lamarckian;
+    }
+    if args.neat {
+        server.strategy = Strategy::Neat(Default::default());
+    }
+    if let Some(spec) = &args.strategy {
+        server.strategy = spec.parse().unwrap_or_else(|e| {
+            eprintln!("--strategy {}: {}", spec, e);
+            std::process::exit(1);
+        });
+    }
+    match args.hidden_namespace {
+        Some(0) => server.hidden_ids = HiddenIds::Unbounded,
+        Some(size) => server.hidden_ids = HiddenIds::Namespace(size),
+        None => {}
+    }
+    if let Some(rate) = args.simplify_rate {
+        server.simplify_rate = rate;
+    }
+    if let Some(dir) = &args.seed {
+        server.seeds = commands::load_genomes(dir);
+    }
+    if let Some(evaluation) = args.evaluation {
+        server.sim.evaluation = evaluation;
+    }
+    if let Some(length) = args.round_length {
+        server.round_length = length;
+    }
+    if let Some(episodes) = args.episodes {
+        server.episodes = episodes;
+    }
+    if let Some(scenario) = args.scenario {
+        server.sim.world.scenario = Some(scenario);
+        for (pool, f) in scenario.fitness() {
+            server.fitness.insert(pool, f);
+        }
+    }
+    let world = world
+        .iter()
+        .map(|change| (Trigger::Generation(0), change.clone()));
+    let events: Vec<(Trigger, Change)> = world
+        .chain(args.schedule.iter().map(|spec| {
+            let event = spec
+                .split_once('=')
+                .ok_or_else(|| "expected <trigger>=<change>".to_string())
+                .and_then(|(trigger, change)| Ok((trigger.parse()?, change.parse()?)));
+            event.unwrap_or_else(|e: String| {
+                eprintln!("--schedule {}: {}", spec, e);
+                std::process::exit(1);
+            })
+        }))
+        .collect();
+    // A resumed run keeps its progress through the same schedule.
+    if !events.is_empty() && events != server.schedule.events {
+        server.schedule = Schedule {
+            events,
+            ..Default::default()
+        };
+    }
+    if let Some(interval) = args.arms_race_interval {
+        server.arms_race_interval = interval;
+    }
+    for spec in &args.fitness {
+        let (pool, f) = per_pool::<FitnessFn>("--fitness", spec);
+        server.fitness.insert(pool, f);
+    }
+    if let Some(cost) = args.node_cost {
+        server.parsimony.node_cost = cost;
+    }
+    if let Some(cost) = args.link_cost {
+        server.parsimony.link_cost = cost;
+    }
+    if args.size_tie_break {
+        server.parsimony.tie_break = true;
+    }
+    if args.max_nodes.is_some() {
+        server.limits.nodes = args.max_nodes;
+    }
+    if args.max_links.is_some() {
+        server.limits.links = args.max_links;
+    }
+    if args.max_hidden.is_some() {
+        server.limits.hidden = args.max_hidden;
+    }
+    if let Some(config) = args.prune.clone() {
+        server.pruning = Some(config.unwrap_or_default());
+    }
+    if let Some(rule) = &args.survival {
+        server.sim.world.survival = rule.clone();
+    }
+    if let Some(migration) = args.pool_migration {
+        server.pool_migration = migration;
+    }
+    let n = match args.pools {
+        Some(n) => n.max(1),
+        None if server.pools.is_empty() => 3,
+        None => server.pools.len(),
+    };
+    server.shrink_pools(n);
+    let mut pools = PoolConfig::defaults(n);
+    for (new, old) in pools.iter_mut().zip(&server.pools) {
+        *new = old.clone();
+    }
+    server.pools = pools;
+    if let Some(scenario) = &server.sim.world.scenario {
+        if scenario.pools().iter().any(|pool| *pool >= n) {
+            eprintln!("the scenario needs pools {:?} but there are {}", scenario.pools(), n);
+            std::process::exit(1);
+        }
+    }
+    for spec in &args.pool {
+        let updated = spec
+            .split_once(':')
+            .ok_or_else(|| "expected <pool>:<config>".to_string())
+            .and_then(|(pool, config)| {
+                let pool = pool.parse::<usize>().map_err(|e| e.to_string())?;
+                match server.pools.get_mut(pool) {
+                    Some(pool) => pool.update(config),
+                    None => Err(format!("there are only {} pools", n)),
+                }
+            });
+        if let Err(e) = updated {
+            eprintln!("--pool {}: {}", spec, e);
+            std::process::exit(1);
+        }
+    }
+    for spec in &args.selection {
+        let (pool, selection) = per_pool::<Selection>("--selection", spec);
+        if pool >= n {
+            eprintln!("--selection {}: there are only {} pools", spec, n);
+            std::process::exit(1);
+        }
+        server
+            .gene_pools
+            .entry(pool)
+            .or_insert_with(GenePool::new)
+            .selection = selection;
+    }
+    for spec in &args.decay {
+        let (pool, decay) = per_pool::<Decay>("--decay", spec);
+        if pool >= n {
+            eprintln!("--decay {}: there are only {} pools", spec, n);
+            std::process::exit(1);
+        }
+        server
+            .gene_pools
+            .entry(pool)
+            .or_insert_with(GenePool::new)
+            .decay = decay;
+    }
 }
 
 fn main() {
     let args = Cli::from_args();
 
-    let mut server: Server =
-        if let Ok(content) = std::fs::read(&args.file.clone().unwrap_or("xxx".into())) {
-            let server: Server = bincode::deserialize(&content).unwrap();
-            server
-        } else {
-            let server = Server::default();
-            server
-        };
+    if let Some(cmd) = args.cmd {
+        cmd.run();
+        return;
+    }
+
+    if let Some(n) = args.islands.filter(|n| *n > 1) {
+        if args.render {
+            eprintln!("--render shows a single server, not --islands");
+            std::process::exit(1);
+        }
+        let mut worlds = vec![vec![]; n];
+        for spec in &args.island_world {
+            let (i, change) = per_pool::<Change>("--island-world", spec);
+            match worlds.get_mut(i) {
+                Some(world) => world.push(change),
+                None => {
+                    eprintln!("--island-world {}: there are only {} islands", spec, n);
+                    std::process::exit(1);
+                }
+            }
+        }
+        let islands = (0..n)
+            .map(|i| {
+                let file = args
+                    .file
+                    .as_ref()
+                    .map(|file| PathBuf::from(format!("{}.island-{}", file.to_string_lossy(), i)));
+                let mut island = file
+                    .as_ref()
+                    .and_then(|file| commands::load(file))
+                    .unwrap_or_default();
+                island.auto_save = file;
+                island.island = Some(i);
+                configure(&mut island, &args, &worlds[i]);
+                island
+            })
+            .collect();
+        let mut archipelago = Archipelago::new(islands);
+        if let Err(e) = archipelago.check_shared() {
+            eprintln!("the island saves disagree: {}", e);
+            std::process::exit(1);
+        }
+        archipelago.topology = args.topology;
+        archipelago.interval = args.migration_interval;
+        archipelago.migrants = args.migrants;
+        if args.migrate_alleles {
+            archipelago.migration = Migration::Alleles;
+        }
+        archipelago.run();
+    }
+
+    let mut server: Server = args
+        .file
+        .as_ref()
+        .and_then(|file| commands::load(file))
+        .unwrap_or_default();
 
-    server.auto_save = args.file;
+    server.auto_save = args.file.clone();
+    configure(&mut server, &args, &[]);
 
     if args.render {
         Render::new(server);
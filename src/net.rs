@@ -1,44 +1,212 @@
 use std::{
     collections::{hash_map::DefaultHasher, HashMap, HashSet},
+    fmt,
     hash::{Hash, Hasher},
+    sync::atomic::{AtomicU32, AtomicUsize, Ordering},
 };
 
-use rand::random;
+use rand::{distributions::Standard, prelude::Distribution, random, Rng};
 use serde::{Deserialize, Serialize};
+use serde_with::serde_as;
 
 use crate::{
     actions::Action,
     genome::{Genome, HasGenome},
     input::Sensor,
+    parsimony,
     pool::{Allele, AlleleID},
     rng::rand_f32,
 };
 
-type NeuronID = String;
+/// Identifier of a hidden neuron, unique within a genome.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct NeuronID(pub u32);
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
-pub struct Neuron {
-    pub charge: f32,
+impl fmt::Display for NeuronID {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "h-{}", self.0)
+    }
+}
+
+/// How new hidden neurons are numbered by mutation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum HiddenIds {
+    /// Random IDs drawn from `0..n`, so independent lineages can converge on
+    /// the same neuron and the gene pools can mix them.
+    Namespace(u32),
+    /// A fresh ID for every new neuron, like NEAT innovation numbers.
+    Unbounded,
+}
+
+impl Default for HiddenIds {
+    fn default() -> Self {
+        HiddenIds::Namespace(10)
+    }
+}
+
+// Zero stands for `HiddenIds::Unbounded`.
+static HIDDEN_NAMESPACE: AtomicU32 = AtomicU32::new(10);
+static NEXT_HIDDEN: AtomicU32 = AtomicU32::new(0);
+
+pub fn set_hidden_ids(ids: HiddenIds) {
+    let size = match ids {
+        HiddenIds::Namespace(size) => size.max(1),
+        HiddenIds::Unbounded => 0,
+    };
+    HIDDEN_NAMESPACE.store(size, Ordering::Relaxed);
+}
+
+static POOLS: AtomicUsize = AtomicUsize::new(3);
+
+/// Sets the number of pools new colours are drawn over.
+pub fn set_pools(pools: usize) {
+    POOLS.store(pools.max(1), Ordering::Relaxed);
+}
+
+pub fn pools() -> usize {
+    POOLS.load(Ordering::Relaxed)
+}
+
+/// Index of the strongest channel of `color`, the first one on ties.
+fn pool_of(color: &[f32]) -> usize {
+    let max = color.iter().cloned().fold(f32::MIN, f32::max);
+    color.iter().position(|c| *c == max).unwrap_or(0)
+}
+
+/// Makes sure unbounded IDs handed out from now on never collide with `id`.
+pub fn reserve_hidden_id(id: NeuronID) {
+    NEXT_HIDDEN.fetch_max(id.0 + 1, Ordering::Relaxed);
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Activation {
+    Tanh,
+    Sigmoid,
+    Relu,
+    Step,
+    Sine,
+    Gaussian,
+}
+
+impl Activation {
+    pub fn apply(&self, x: f32) -> f32 {
+        match self {
+            Activation::Tanh => x.tanh(),
+            Activation::Sigmoid => 1.0 / (1.0 + (-x).exp()),
+            Activation::Relu => x.max(0.0),
+            Activation::Step => {
+                if x > 0.0 {
+                    1.0
+                } else {
+                    0.0
+                }
+            }
+            Activation::Sine => x.sin(),
+            Activation::Gaussian => (-x * x).exp(),
+        }
+    }
+}
+
+impl Distribution<Activation> for Standard {
+    fn sample<R: Rng + ?Sized>(&self, _rng: &mut R) -> Activation {
+        let activations = [
+            Activation::Tanh,
+            Activation::Sigmoid,
+            Activation::Relu,
+            Activation::Step,
+            Activation::Sine,
+            Activation::Gaussian,
+        ];
+        let i = random::<usize>() % activations.len();
+        *activations.get(i).unwrap()
+    }
+}
+
+/// Evolvable dynamics of a single neuron, carried by its `NeuralNode` gene.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct NeuronParams {
+    pub activation: Activation,
+    /// Fraction of the charge kept from one tick to the next.
+    pub leak: f32,
+    /// Minimum absolute output for an action neuron to fire.
+    pub threshold: f32,
+    pub bias: f32,
+}
+
+impl Default for NeuronParams {
+    fn default() -> Self {
+        Self {
+            activation: Activation::Tanh,
+            leak: 0.5,
+            threshold: 0.9,
+            bias: 0.0,
+        }
+    }
 }
-impl Default for Neuron {
-    fn default() -> Neuron {
-        Self { charge: 0.0 }
+
+impl Hash for NeuronParams {
+    fn hash<H>(&self, state: &mut H)
+    where
+        H: Hasher,
+    {
+        self.activation.hash(state);
+        ((self.leak * 100.0).round() as i32).hash(state);
+        ((self.threshold * 100.0).round() as i32).hash(state);
+        ((self.bias * 100.0).round() as i32).hash(state);
     }
 }
 
+impl NeuronParams {
+    pub fn mutate(&mut self) {
+        match random::<usize>() % 4 {
+            0 => self.activation = random(),
+            1 => self.leak = (self.leak + rand_f32() * 0.2).min(1.0).max(0.0),
+            2 => self.threshold = (self.threshold + rand_f32() * 0.2).min(1.0).max(0.05),
+            _ => self.bias += rand_f32() * 0.2,
+        }
+    }
+    pub fn mix(&self, partner: &Self) -> Self {
+        Self {
+            activation: if random() {
+                self.activation
+            } else {
+                partner.activation
+            },
+            leak: if random() { self.leak } else { partner.leak },
+            threshold: if random() {
+                self.threshold
+            } else {
+                partner.threshold
+            },
+            bias: if random() { self.bias } else { partner.bias },
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Neuron {
+    pub charge: f32,
+    pub params: NeuronParams,
+}
+
 impl Neuron {
+    pub fn new(params: NeuronParams) -> Self {
+        Self {
+            charge: 0.0,
+            params,
+        }
+    }
     pub fn discharge(&mut self) {
-        self.charge *= 0.5;
+        self.charge *= self.params.leak;
     }
     pub fn output(&self) -> f32 {
-        self.charge.tanh()
+        self.params.activation.apply(self.charge + self.params.bias)
     }
     pub fn add(&mut self, num: f32) {
         self.charge += num;
     }
     fn fire(&mut self) -> bool {
-        let out = self.output();
-        if self.output().abs() < 0.9 {
+        if self.output().abs() < self.params.threshold {
             false
         } else {
             self.charge = 0.0;
@@ -47,34 +215,156 @@ impl Neuron {
     }
 }
 
+/// Coefficients of the generalised Hebbian rule applied to a plastic link:
+/// `dw = rate * (a * pre * post + b * pre + c * post + d)`.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct Hebbian {
+    pub rate: f32,
+    pub a: f32,
+    pub b: f32,
+    pub c: f32,
+    pub d: f32,
+}
+
+impl Hash for Hebbian {
+    fn hash<H>(&self, state: &mut H)
+    where
+        H: Hasher,
+    {
+        for x in [self.rate, self.a, self.b, self.c, self.d] {
+            ((x * 100.0).round() as i32).hash(state);
+        }
+    }
+}
+
+impl Distribution<Hebbian> for Standard {
+    fn sample<R: Rng + ?Sized>(&self, _rng: &mut R) -> Hebbian {
+        Hebbian {
+            rate: random::<f32>() * 0.1,
+            a: rand_f32(),
+            b: rand_f32(),
+            c: rand_f32(),
+            d: rand_f32() * 0.1,
+        }
+    }
+}
+
+impl Hebbian {
+    fn delta(&self, pre: f32, post: f32) -> f32 {
+        self.rate * (self.a * pre * post + self.b * pre + self.c * post + self.d)
+    }
+    fn mutate(&mut self) {
+        let x = match random::<usize>() % 5 {
+            0 => {
+                self.rate = (self.rate + rand_f32() * 0.02).max(0.0);
+                return;
+            }
+            1 => &mut self.a,
+            2 => &mut self.b,
+            3 => &mut self.c,
+            _ => &mut self.d,
+        };
+        *x += rand_f32() * 0.2;
+    }
+}
+
+/// How the weights learned during a lifetime are passed on by `to_genome`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Inheritance {
+    /// Learned weights are discarded, offspring start from the innate ones.
+    Baldwinian,
+    /// Learned weights are written back into the genome.
+    Lamarckian,
+}
+
+impl Default for Inheritance {
+    fn default() -> Self {
+        Inheritance::Baldwinian
+    }
+}
+
+/// Bound on the magnitude a plastic link's weight can learn.
+pub const MAX_WEIGHT: f32 = 8.0;
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct NeuralLink {
     pub inverse: bool,
     pub weight: f32,
+    #[serde(default)]
+    pub plastic: Option<Hebbian>,
+    /// Weight change learned during the current lifetime.
+    #[serde(default)]
+    pub learned: f32,
+    /// Weight before Lamarckian inheritance wrote learned changes into it,
+    /// `None` while nothing was written back.
+    #[serde(default)]
+    pub innate: Option<f32>,
 }
 
+/// Learned changes written back by Lamarckian inheritance are left out, so
+/// a plastic link stays the same allele from one generation to the next.
 impl Hash for NeuralLink {
     fn hash<H>(&self, state: &mut H)
     where
         H: Hasher,
     {
+        let weight = self.innate.unwrap_or(self.weight);
         self.inverse.hash(state);
-        ((self.weight * 100.0).round() as i32).hash(state);
+        ((weight * 100.0).round() as i32).hash(state);
+        self.plastic.hash(state);
     }
 }
 
 impl NeuralLink {
-    fn new() -> Self {
+    pub fn new() -> Self {
         Self {
             weight: rand_f32() * 4.0,
             inverse: random(),
+            plastic: if random::<f32>() > 0.8 {
+                Some(random())
+            } else {
+                None
+            },
+            learned: 0.0,
+            innate: None,
         }
     }
     fn output(&self, mut x: f32) -> f32 {
         if self.inverse {
             x = 1.0 - x;
         }
-        (x * self.weight).tan()
+        (x * (self.weight + self.learned)).tanh()
+    }
+    fn learn(&mut self, pre: f32, post: f32) {
+        if let Some(rule) = &self.plastic {
+            let weight = (self.weight + self.learned + rule.delta(pre, post))
+                .min(MAX_WEIGHT)
+                .max(-MAX_WEIGHT);
+            self.learned = weight - self.weight;
+        }
+    }
+    fn inherit(&mut self, inheritance: Inheritance) {
+        if inheritance == Inheritance::Lamarckian {
+            self.innate.get_or_insert(self.weight);
+            self.weight += self.learned;
+        }
+        self.learned = 0.0;
+    }
+    fn mutate(&mut self) {
+        match (random::<usize>() % 4, &mut self.plastic) {
+            (0, plastic) => {
+                *plastic = if plastic.is_some() {
+                    None
+                } else {
+                    Some(random())
+                }
+            }
+            (1, Some(rule)) => rule.mutate(),
+            _ => {
+                self.weight += rand_f32() * 0.5;
+                self.innate = None;
+            }
+        }
     }
 }
 
@@ -101,12 +391,21 @@ impl NetNodes {
 }
 
 fn rand_h() -> NeuronID {
-    format!("h-{}", random::<usize>() % 10)
+    match HIDDEN_NAMESPACE.load(Ordering::Relaxed) {
+        0 => NeuronID(NEXT_HIDDEN.fetch_add(1, Ordering::Relaxed)),
+        size => NeuronID(random::<u32>() % size),
+    }
 }
 
+// Maps keyed by enums are stored as lists of pairs so that they can be
+// written to JSON, which only allows string keys.
+#[serde_as]
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct NeuralNode {
+    #[serde_as(as = "Vec<(_, _)>")]
     pub inputs: HashMap<NeuralSource, NeuralLink>,
+    #[serde(default)]
+    pub params: NeuronParams,
 }
 impl Hash for NeuralNode {
     fn hash<H>(&self, state: &mut H)
@@ -117,6 +416,31 @@ impl Hash for NeuralNode {
             target.hash(state);
             link.hash(state);
         }
+        self.params.hash(state);
+    }
+}
+
+impl NeuralNode {
+    pub fn new() -> Self {
+        Self {
+            inputs: HashMap::new(),
+            params: Default::default(),
+        }
+    }
+    fn mix(&self, partner: &Self) -> Self {
+        let mut inputs = self.inputs.clone();
+        for (source, link) in &partner.inputs {
+            let shared = inputs.contains_key(source);
+            // Hidden inputs are only crossed over when both parents have them,
+            // otherwise the child would reference a neuron it does not own.
+            if (shared && random()) || (!shared && matches!(source, NeuralSource::Sensor(_))) {
+                inputs.insert(source.clone(), link.clone());
+            }
+        }
+        Self {
+            inputs,
+            params: self.params.mix(&partner.params),
+        }
     }
 }
 
@@ -130,11 +454,16 @@ impl Allele<NeuralTarget> for NeuralNode {
         self.inputs
             .iter()
             .filter_map(|(target, _link)| match target {
-                NeuralSource::Hidden(x) => Some(NeuralTarget::Hidden(x.clone())),
+                NeuralSource::Hidden(x) => Some(NeuralTarget::Hidden(*x)),
                 _ => None,
             })
             .collect()
     }
+    fn drop_requirement(&mut self, gene: &NeuralTarget) {
+        if let NeuralTarget::Hidden(id) = gene {
+            self.inputs.remove(&NeuralSource::Hidden(*id));
+        }
+    }
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -143,32 +472,36 @@ pub struct Net {
     pub nodes: HashMap<NeuralTarget, NeuralNode>,
     pub sensors: HashMap<Sensor, f32>,
     pub state: HashMap<NeuralTarget, Neuron>,
-    pub color: [f32; 3],
+    /// Pool the replicant belongs to, inherited from its first parent.
+    pub pool: usize,
+    /// Visual trait, the pool only follows it under
+    /// `PoolMigration::Colour`.
+    pub color: Vec<f32>,
+    pub inheritance: Inheritance,
+    /// State of the previous tick, kept to reuse its allocation.
+    #[serde(skip)]
+    previous: HashMap<NeuralTarget, Neuron>,
 }
 
 impl Default for Net {
     fn default() -> Self {
-        let mut color = [0.0; 3];
-        *color.get_mut(random::<usize>() % 3).unwrap() = random::<f32>().abs();
+        let mut color = vec![0.0; pools()];
+        let i = random::<usize>() % color.len();
+        color[i] = random::<f32>().abs();
         Self {
+            pool: i,
             color,
             nodes: Default::default(),
             state: Default::default(),
             sensors: Default::default(),
+            inheritance: Default::default(),
+            previous: Default::default(),
         }
     }
 }
 impl Net {
     pub fn pool(&self) -> usize {
-        let c = self.color;
-        let max = c[0].max(c[1]).max(c[2]);
-        if c[0] == max {
-            0
-        } else if c[1] == max {
-            1
-        } else {
-            2
-        }
+        self.pool
     }
     // pub fn links(
     //     &self,
@@ -298,32 +631,33 @@ impl Net {
     //     };
     // }
     pub fn tick(&mut self) -> Vec<Action> {
-        let state = self.state.clone();
+        self.previous.clone_from(&self.state);
         self.discharge();
         // input.iter().for_each(|(sensor, x)| {
         //     let links = clone.get(sensor).unwrap();
         //     self.apply_synapse(*x, &links.outputs);
         // });
+        let previous = &self.previous;
+        let sensors = &self.sensors;
+        let input = |source: &NeuralSource| match source {
+            NeuralSource::Hidden(hid) => previous[&NeuralTarget::Hidden(*hid)].output(),
+            NeuralSource::Sensor(sensor) => sensors[sensor],
+        };
         let mut actions = vec![];
-        self.nodes.iter().for_each(|(target, node)| {
+        self.nodes.iter_mut().for_each(|(target, node)| {
             let sum = node
                 .inputs
                 .iter()
-                .map(|(source, link)| {
-                    let mut x = match source {
-                        NeuralSource::Hidden(hid) => {
-                            state[&NeuralTarget::Hidden(hid.clone())].output()
-                        }
-                        NeuralSource::Sensor(sensor) => *self.sensors.get(sensor).unwrap(),
-                    };
-                    if link.inverse {
-                        x = 1.0 - x;
-                    }
-                    (x * link.weight).tanh()
-                })
-                .sum::<f32>() / node.inputs.len() as f32;
+                .map(|(source, link)| link.output(input(source)))
+                .sum::<f32>()
+                / node.inputs.len() as f32;
             let state = self.state.get_mut(target).unwrap();
             state.add(sum);
+            let post = state.output();
+            node.inputs
+                .iter_mut()
+                .filter(|(_, link)| link.plastic.is_some())
+                .for_each(|(source, link)| link.learn(input(source), post));
             if let NeuralTarget::Action(action) = target {
                 if state.fire() {
                     actions.push(action.clone());
@@ -359,9 +693,15 @@ impl Net {
     }
 }
 
-#[derive(Default, Clone, Serialize)]
+#[serde_as]
+#[derive(Default, Clone, Serialize, Deserialize)]
 pub struct NetGenome {
-    pub color: [f32; 3],
+    /// Missing from JSON genomes written before pools were explicit,
+    /// `from_json` then takes the pool of the colour.
+    #[serde(default)]
+    pub pool: usize,
+    pub color: Vec<f32>,
+    #[serde_as(as = "Vec<(_, _)>")]
     pub nodes: HashMap<NeuralTarget, NeuralNode>,
 }
 
@@ -378,39 +718,49 @@ pub enum NeuralTarget {
 }
 
 impl NetGenome {
-    pub fn value(&self) -> serde_json::Value {
-        let mut value = serde_json::json!({});
-        value["color"] = serde_json::to_value(self.color).unwrap();
-        let links: Vec<_> = self
-            .nodes
-            .iter()
-            .map(|(source, links)| {
-                (
-                    source,
-                    links
-                        .inputs
-                        .iter()
-                        .map(|(i, v)| (i.clone(), v.clone()))
-                        .collect::<Vec<_>>(),
-                )
-            })
-            .collect();
-        value["links"] = serde_json::to_value(links).unwrap();
-        value
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap()
+    }
+    /// Reads a genome written by `to_json`, repairing whatever a manual edit
+    /// may have broken. Genomes written before pools were explicit join the
+    /// pool of their colour.
+    pub fn from_json(json: &str) -> serde_json::Result<Self> {
+        let value: serde_json::Value = serde_json::from_str(json)?;
+        let explicit_pool = value.get("pool").is_some();
+        let mut genome: NetGenome = serde_json::from_value(value)?;
+        if !explicit_pool {
+            genome.pool = genome.color_pool();
+        }
+        genome.repair();
+        Ok(genome)
     }
 }
 impl HasGenome<NetGenome> for Net {
     fn to_genome(&self) -> NetGenome {
+        let mut nodes = self.nodes.clone();
+        nodes
+            .values_mut()
+            .flat_map(|node| node.inputs.values_mut())
+            .for_each(|link| link.inherit(self.inheritance));
         NetGenome {
+            pool: self.pool,
             color: self.color.clone(),
-            nodes: self.nodes.clone(),
+            nodes,
         }
     }
 
     fn from_genome(genome: &NetGenome) -> Self {
+        debug_assert!(
+            genome.validate().is_empty(),
+            "invalid genome: {:?}",
+            genome.validate()
+        );
         Net {
+            pool: genome.pool,
             color: genome.color.clone(),
             nodes: genome.nodes.clone(),
+            inheritance: Default::default(),
+            previous: Default::default(),
             sensors: genome
                 .nodes
                 .values()
@@ -423,14 +773,17 @@ impl HasGenome<NetGenome> for Net {
                 .collect(),
             state: genome
                 .nodes
-                .keys()
-                .map(|target| (target.clone(), Neuron::default()))
+                .iter()
+                .map(|(target, node)| (target.clone(), Neuron::new(node.params)))
                 .collect(),
         }
     }
 }
 impl NetGenome {
     pub fn randomize_color(&mut self) {
+        if self.color.len() < pools() {
+            self.color.resize(pools(), 0.0);
+        }
         let i = random::<usize>() % self.color.len();
         let c = self.color.get_mut(i).unwrap();
         *c += (random::<f32>() * 2.0 - 1.0) * 0.2;
@@ -441,7 +794,7 @@ impl NetGenome {
             *c = c.abs();
         }
         *c = c.min(1.0).max(0.0);
-        let max = self.color[0].max(self.color[1]).max(self.color[2]);
+        let max = self.color.iter().cloned().fold(f32::MIN, f32::max);
         for x in &mut self.color {
             if *x != max {
                 *x -= 0.01;
@@ -451,9 +804,7 @@ impl NetGenome {
     }
     pub fn randomize(&mut self) {
         if self.nodes.is_empty() || random::<f32>() > 0.9 {
-            let mut node = NeuralNode {
-                inputs: HashMap::new(),
-            };
+            let mut node = NeuralNode::new();
             node.inputs
                 .insert(NeuralSource::Sensor(random()), NeuralLink::new());
             if random() {
@@ -462,13 +813,11 @@ impl NetGenome {
             } else {
                 // Add hid <- random sensor
                 let hid = rand_h();
-                let target = NeuralTarget::Hidden(hid.clone());
+                let target = NeuralTarget::Hidden(hid);
                 self.nodes.insert(target, node);
 
                 // Add random action <- hid
-                let mut node2 = NeuralNode {
-                    inputs: HashMap::new(),
-                };
+                let mut node2 = NeuralNode::new();
                 node2
                     .inputs
                     .insert(NeuralSource::Hidden(hid), NeuralLink::new());
@@ -478,25 +827,132 @@ impl NetGenome {
             let keys: Vec<_> = self.nodes.keys().collect();
             let target = keys[random::<usize>() % keys.len()].clone();
             let node = self.nodes.get_mut(&target).unwrap();
-            node.inputs
-                .insert(NeuralSource::Sensor(random()), NeuralLink::new());
+            match random::<usize>() % 3 {
+                0 => {
+                    node.inputs
+                        .insert(NeuralSource::Sensor(random()), NeuralLink::new());
+                }
+                1 => node.params.mutate(),
+                _ => {
+                    let links: Vec<_> = node.inputs.keys().cloned().collect();
+                    let source = &links[random::<usize>() % links.len()];
+                    node.inputs.get_mut(source).unwrap().mutate();
+                }
+            }
         }
+        self.repair();
+        self.limit(&parsimony::limits());
     }
     pub fn pool(&self) -> usize {
-        let c = self.color;
-        let max = c[0].max(c[1]).max(c[2]);
-        if c[0] == max {
-            0
-        } else if c[1] == max {
-            1
-        } else {
-            2
+        self.pool
+    }
+    /// Pool the colour leans to.
+    pub fn color_pool(&self) -> usize {
+        pool_of(&self.color)
+    }
+    /// Moves the genome to `pool`, with a colour that is all `pool`'s.
+    pub fn set_pool(&mut self, pool: usize) {
+        self.pool = pool;
+        self.color = vec![0.0; pools().max(pool + 1)];
+        self.color[pool] = 1.0;
+    }
+}
+/// Inconsistencies that `NetGenome::validate` reports.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum GenomeIssue {
+    /// `target` reads from a hidden neuron that has no node.
+    DanglingInput {
+        target: NeuralTarget,
+        hidden: NeuronID,
+    },
+    /// A hidden neuron whose output never reaches an action.
+    UnreachableHidden(NeuronID),
+    /// A node without inputs, which would divide by zero in `Net::tick`.
+    EmptyNode(NeuralTarget),
+}
+
+impl NetGenome {
+    /// Nodes whose output reaches at least one action, actions included.
+    fn contributing(&self) -> HashSet<NeuralTarget> {
+        let mut found = HashSet::new();
+        let mut queue: Vec<_> = self
+            .nodes
+            .keys()
+            .filter(|target| matches!(target, NeuralTarget::Action(_)))
+            .cloned()
+            .collect();
+        while let Some(target) = queue.pop() {
+            if !found.insert(target.clone()) {
+                continue;
+            }
+            if let Some(node) = self.nodes.get(&target) {
+                queue.extend(node.get_gene_requirements());
+            }
+        }
+        found
+    }
+    pub fn validate(&self) -> Vec<GenomeIssue> {
+        let mut issues = vec![];
+        for (target, node) in &self.nodes {
+            if node.inputs.is_empty() {
+                issues.push(GenomeIssue::EmptyNode(target.clone()));
+            }
+            for source in node.inputs.keys() {
+                if let NeuralSource::Hidden(hidden) = source {
+                    if !self.nodes.contains_key(&NeuralTarget::Hidden(*hidden)) {
+                        issues.push(GenomeIssue::DanglingInput {
+                            target: target.clone(),
+                            hidden: *hidden,
+                        });
+                    }
+                }
+            }
+        }
+        let contributing = self.contributing();
+        for target in self.nodes.keys() {
+            if let NeuralTarget::Hidden(hidden) = target {
+                if !contributing.contains(target) {
+                    issues.push(GenomeIssue::UnreachableHidden(*hidden));
+                }
+            }
+        }
+        issues
+    }
+    /// Removes whatever `validate` complains about. Dropping a node can leave
+    /// new dangling inputs behind, so this runs until the genome is clean.
+    pub fn repair(&mut self) {
+        loop {
+            let issues = self.validate();
+            if issues.is_empty() {
+                return;
+            }
+            for issue in issues {
+                match issue {
+                    GenomeIssue::DanglingInput { target, hidden } => {
+                        if let Some(node) = self.nodes.get_mut(&target) {
+                            node.inputs.remove(&NeuralSource::Hidden(hidden));
+                        }
+                    }
+                    GenomeIssue::UnreachableHidden(hidden) => {
+                        self.nodes.remove(&NeuralTarget::Hidden(hidden));
+                    }
+                    GenomeIssue::EmptyNode(target) => {
+                        self.nodes.remove(&target);
+                    }
+                }
+            }
         }
     }
 }
+
 impl Genome for NetGenome {
     fn mix(&self, p2: &NetGenome) -> NetGenome {
         let mut ret = self.clone();
+        for (target, node) in &p2.nodes {
+            if let Some(own) = ret.nodes.get_mut(target) {
+                *own = own.mix(node);
+            }
+        }
         // // let i = self.pool();
         // let i = random::<usize>() % 3;
         // // ret.color[i] = ret.color[i] * 0.3 + p2.color[i] * 0.7;
@@ -508,6 +964,7 @@ impl Genome for NetGenome {
         //         ret.links.insert(i.clone(), links.clone());
         //     }
         // }
+        ret.repair();
         ret
     }
 }
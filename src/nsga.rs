@@ -0,0 +1,116 @@
+// Non-dominated sorting and crowding distance of NSGA-II (Deb et al. 2002).
+// Every objective is maximised.
+
+use std::cmp::Ordering;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{fitness::Objective, simulation::Simulation};
+
+/// Whether `a` is at least as good as `b` on every objective and better on
+/// one.
+pub fn dominates(a: &[f32], b: &[f32]) -> bool {
+    a.iter().zip(b).all(|(x, y)| x >= y) && a.iter().zip(b).any(|(x, y)| x > y)
+}
+
+/// Splits `values` into Pareto fronts, the non-dominated one first.
+pub fn non_dominated_sort(values: &[Vec<f32>]) -> Vec<Vec<usize>> {
+    let n = values.len();
+    let mut dominated: Vec<Vec<usize>> = vec![vec![]; n];
+    let mut domination_count = vec![0; n];
+    let mut fronts = vec![vec![]];
+    for p in 0..n {
+        for q in 0..n {
+            if dominates(&values[p], &values[q]) {
+                dominated[p].push(q);
+            } else if dominates(&values[q], &values[p]) {
+                domination_count[p] += 1;
+            }
+        }
+        if domination_count[p] == 0 {
+            fronts[0].push(p);
+        }
+    }
+    let mut i = 0;
+    while !fronts[i].is_empty() {
+        let mut next = vec![];
+        for p in &fronts[i] {
+            for q in &dominated[*p] {
+                domination_count[*q] -= 1;
+                if domination_count[*q] == 0 {
+                    next.push(*q);
+                }
+            }
+        }
+        fronts.push(next);
+        i += 1;
+    }
+    fronts.pop();
+    fronts
+}
+
+/// Crowding distance of each member of `front`, in the same order. The
+/// extremes of every objective get an infinite distance.
+pub fn crowding_distance(values: &[Vec<f32>], front: &[usize]) -> Vec<f32> {
+    let mut distance = vec![0.0; front.len()];
+    if front.is_empty() {
+        return distance;
+    }
+    for o in 0..values[front[0]].len() {
+        let mut order: Vec<usize> = (0..front.len()).collect();
+        order.sort_by(|a, b| {
+            values[front[*a]][o]
+                .partial_cmp(&values[front[*b]][o])
+                .unwrap_or(Ordering::Equal)
+        });
+        let min = values[front[order[0]]][o];
+        let max = values[front[*order.last().unwrap()]][o];
+        distance[order[0]] = f32::INFINITY;
+        distance[*order.last().unwrap()] = f32::INFINITY;
+        if max - min <= 0.0 {
+            continue;
+        }
+        for w in order.windows(3) {
+            distance[w[1]] += (values[front[w[2]]][o] - values[front[w[0]]][o]) / (max - min);
+        }
+    }
+    distance
+}
+
+/// Summary of one objective over the population.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ObjectiveStats {
+    pub objective: Objective,
+    pub mean: f32,
+    pub best: f32,
+    /// Mean over the first Pareto front.
+    pub front_mean: f32,
+}
+
+pub fn objective_stats(objectives: &[Objective], sim: &Simulation) -> Vec<ObjectiveStats> {
+    let values: Vec<Vec<f32>> = sim
+        .replicants
+        .iter()
+        .map(|rep| objectives.iter().map(|o| o.value(rep, sim)).collect())
+        .collect();
+    let front = non_dominated_sort(&values)
+        .into_iter()
+        .next()
+        .unwrap_or_default();
+    objectives
+        .iter()
+        .enumerate()
+        .map(|(o, objective)| {
+            let mean = |members: &mut dyn Iterator<Item = &Vec<f32>>| {
+                let (sum, n) = members.fold((0.0, 0), |(sum, n), v| (sum + v[o], n + 1));
+                sum / n.max(1) as f32
+            };
+            ObjectiveStats {
+                objective: objective.clone(),
+                mean: mean(&mut values.iter()),
+                best: values.iter().map(|v| v[o]).fold(f32::MIN, f32::max),
+                front_mean: mean(&mut front.iter().map(|i| &values[*i])),
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,122 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use serde::{Deserialize, Serialize};
+
+use crate::net::{Net, NetGenome, NeuralNode, NeuralSource, NeuralTarget};
+
+/// Fitness cost of network size.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Parsimony {
+    pub node_cost: f32,
+    pub link_cost: f32,
+    /// Between equally fit genomes, strategies prefer the one with fewer
+    /// links.
+    pub tie_break: bool,
+}
+
+impl Parsimony {
+    pub fn penalty(&self, net: &Net) -> f32 {
+        let (nodes, links) = size(&net.nodes);
+        self.node_cost * nodes as f32 + self.link_cost * links as f32
+    }
+}
+
+/// Number of nodes and links.
+pub fn size<'a>(
+    nodes: impl IntoIterator<Item = (&'a NeuralTarget, &'a NeuralNode)>,
+) -> (usize, usize) {
+    nodes.into_iter().fold((0, 0), |(nodes, links), (_, node)| {
+        (nodes + 1, links + node.inputs.len())
+    })
+}
+
+/// Hard caps on genome size, `None` for no cap.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Limits {
+    pub nodes: Option<usize>,
+    pub links: Option<usize>,
+    pub hidden: Option<usize>,
+}
+
+// usize::MAX stands for no cap.
+static MAX_NODES: AtomicUsize = AtomicUsize::new(usize::MAX);
+static MAX_LINKS: AtomicUsize = AtomicUsize::new(usize::MAX);
+static MAX_HIDDEN: AtomicUsize = AtomicUsize::new(usize::MAX);
+
+/// Sets the caps `NetGenome::randomize` enforces.
+pub fn set_limits(limits: Limits) {
+    MAX_NODES.store(limits.nodes.unwrap_or(usize::MAX), Ordering::Relaxed);
+    MAX_LINKS.store(limits.links.unwrap_or(usize::MAX), Ordering::Relaxed);
+    MAX_HIDDEN.store(limits.hidden.unwrap_or(usize::MAX), Ordering::Relaxed);
+}
+
+pub fn limits() -> Limits {
+    let cap = |max: &AtomicUsize| Some(max.load(Ordering::Relaxed)).filter(|n| *n != usize::MAX);
+    Limits {
+        nodes: cap(&MAX_NODES),
+        links: cap(&MAX_LINKS),
+        hidden: cap(&MAX_HIDDEN),
+    }
+}
+
+impl NetGenome {
+    /// Shrinks the genome under `limits`: hidden neurons go first, then the
+    /// weakest links. Actions are never removed to meet the node cap.
+    pub fn limit(&mut self, limits: &Limits) {
+        loop {
+            let hidden: Vec<NeuralTarget> = self
+                .nodes
+                .keys()
+                .filter(|target| matches!(target, NeuralTarget::Hidden(_)))
+                .cloned()
+                .collect();
+            let too_many_hidden = limits.hidden.map_or(false, |max| hidden.len() > max);
+            let too_many_nodes = limits.nodes.map_or(false, |max| self.nodes.len() > max);
+            if hidden.is_empty() || !(too_many_hidden || too_many_nodes) {
+                break;
+            }
+            // The neuron feeding the fewest links is the cheapest to lose.
+            let uses = |target: &NeuralTarget| {
+                let source = match target {
+                    NeuralTarget::Hidden(id) => NeuralSource::Hidden(*id),
+                    NeuralTarget::Action(_) => unreachable!(),
+                };
+                self.nodes
+                    .values()
+                    .filter(|node| node.inputs.contains_key(&source))
+                    .count()
+            };
+            let victim = hidden
+                .iter()
+                .min_by_key(|target| uses(target))
+                .unwrap()
+                .clone();
+            self.nodes.remove(&victim);
+            self.repair();
+        }
+
+        while let Some(max) = limits.links {
+            let (_, links) = size(&self.nodes);
+            if links <= max {
+                break;
+            }
+            let weakest = self
+                .nodes
+                .iter()
+                .flat_map(|(target, node)| {
+                    node.inputs
+                        .iter()
+                        .map(move |(source, link)| (target, source, link.weight.abs()))
+                })
+                .min_by(|a, b| a.2.partial_cmp(&b.2).unwrap_or(std::cmp::Ordering::Equal))
+                .map(|(target, source, _)| (target.clone(), source.clone()));
+            match weakest {
+                Some((target, source)) => {
+                    self.nodes.get_mut(&target).unwrap().inputs.remove(&source);
+                    self.repair();
+                }
+                None => break,
+            }
+        }
+    }
+}
@@ -1,19 +1,396 @@
-use std::{cmp::Ordering, collections::HashMap, hash::Hash};
+use std::{
+    cmp::Ordering,
+    collections::{HashMap, HashSet, VecDeque},
+    hash::Hash,
+    str::FromStr,
+};
 
 use rand::random;
 use serde::{Deserialize, Serialize};
 
+use crate::rng::rand_normal;
+
 #[derive(Default, Clone, Serialize, Deserialize)]
 pub struct Score {
     avg: f32,
     tot: f32,
+    /// Sum of the squared scores, for the variance.
+    sq: f32,
+    /// Evaluations the average is made of, fractional once decayed.
+    weight: f32,
+    /// Evaluations ever recorded.
     count: usize,
+    first_seen: usize,
+    last_seen: usize,
+    /// Totals of the generations still inside a `Decay::Window`.
+    recent: VecDeque<Bucket>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct Bucket {
+    generation: usize,
+    tot: f32,
+    sq: f32,
+    weight: f32,
 }
+
 impl Score {
-    pub fn record(&mut self, score: f32) {
+    /// Score of `count` evaluations summing to `tot` whose spread is unknown.
+    /// The standard deviation starts at 1, as for an allele evaluated less
+    /// than twice, rather than 0.
+    pub fn new(tot: f32, count: usize) -> Self {
+        let avg = if count > 0 { tot / count as f32 } else { 0.0 };
+        Self {
+            avg,
+            tot,
+            sq: avg * tot + (count as f32 - 1.0).max(0.0),
+            weight: count as f32,
+            count,
+            ..Default::default()
+        }
+    }
+    pub fn record(&mut self, score: f32, generation: usize) {
+        if self.count == 0 {
+            self.first_seen = generation;
+        }
+        self.last_seen = generation;
         self.tot += score;
+        self.sq += score * score;
+        self.weight += 1.0;
         self.count += 1;
-        self.avg = self.tot / self.count as f32;
+        self.avg = self.tot / self.weight;
+
+        match self.recent.back_mut() {
+            Some(bucket) if bucket.generation == generation => {
+                bucket.tot += score;
+                bucket.sq += score * score;
+                bucket.weight += 1.0;
+            }
+            _ => self.recent.push_back(Bucket {
+                generation,
+                tot: score,
+                sq: score * score,
+                weight: 1.0,
+            }),
+        }
+    }
+    /// Applies `decay` for the generations between `from` and `to`.
+    pub fn age(&mut self, decay: &Decay, from: usize, to: usize) {
+        match decay {
+            Decay::Exponential { factor } => {
+                let f = factor.powi(to.saturating_sub(from) as i32);
+                self.tot *= f;
+                self.sq *= f;
+                self.weight *= f;
+                self.recent.clear();
+            }
+            Decay::Window { generations } => {
+                while let Some(bucket) = self.recent.front() {
+                    if bucket.generation + generations > to {
+                        break;
+                    }
+                    self.tot -= bucket.tot;
+                    self.sq -= bucket.sq;
+                    self.weight -= bucket.weight;
+                    self.recent.pop_front();
+                }
+                if self.recent.is_empty() {
+                    self.tot = 0.0;
+                    self.sq = 0.0;
+                    self.weight = 0.0;
+                }
+            }
+            Decay::Lifetime | Decay::AgeDiscount { .. } => self.recent.clear(),
+        }
+        // Without evaluations left the last average is kept.
+        if self.weight > 0.0 {
+            self.avg = self.tot / self.weight;
+        }
+    }
+    pub fn avg(&self) -> f32 {
+        self.avg
+    }
+    pub fn count(&self) -> usize {
+        self.count
+    }
+    /// Evaluations still counted by the decay, equal to `count` without one.
+    pub fn weight(&self) -> f32 {
+        self.weight
+    }
+    /// Generation the allele was first evaluated in.
+    pub fn first_seen(&self) -> usize {
+        self.first_seen
+    }
+    /// Generation the allele was last evaluated in.
+    pub fn last_seen(&self) -> usize {
+        self.last_seen
+    }
+    /// Standard deviation of the recorded scores, 1 until there are two.
+    pub fn std(&self) -> f32 {
+        if self.weight < 2.0 {
+            return 1.0;
+        }
+        let n = self.weight;
+        ((self.sq - self.tot * self.tot / n) / (n - 1.0))
+            .max(0.0)
+            .sqrt()
+    }
+}
+
+/// How older evaluations lose weight in a `Score`, so averages can follow a
+/// changing environment.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Decay {
+    /// Every evaluation counts the same forever.
+    Lifetime,
+    /// Evaluations are multiplied by `factor` every generation.
+    Exponential { factor: f32 },
+    /// Only evaluations of the last `generations` generations count.
+    Window { generations: usize },
+    /// When alleles are compared, the gap between an average and the mean
+    /// of its gene is halved every `half_life` generations the allele goes
+    /// unevaluated.
+    AgeDiscount { half_life: f32 },
+}
+
+impl Default for Decay {
+    fn default() -> Self {
+        Decay::Lifetime
+    }
+}
+
+impl Decay {
+    /// Value alleles are compared by at `generation`, `mean` being the mean
+    /// average of the alleles of the gene.
+    pub fn value(&self, score: &Score, generation: usize, mean: f32) -> f32 {
+        match self {
+            Decay::AgeDiscount { half_life } => {
+                let age = generation.saturating_sub(score.last_seen) as f32;
+                mean + (score.avg - mean) * 0.5f32.powf(age / half_life.max(1e-6))
+            }
+            _ => score.avg,
+        }
+    }
+    /// `value` of each of the alleles of a gene.
+    pub fn values<A>(
+        &self,
+        alleles: &HashMap<AlleleID, (A, Score)>,
+        generation: usize,
+    ) -> HashMap<AlleleID, f32> {
+        let mean = alleles.values().map(|(_, s)| s.avg).sum::<f32>() / alleles.len().max(1) as f32;
+        alleles
+            .iter()
+            .map(|(id, (_, s))| (*id, self.value(s, generation, mean)))
+            .collect()
+    }
+}
+
+/// Parses `lifetime`, `ema:<factor>`, `window:<generations>` or
+/// `age:<half life>`.
+impl FromStr for Decay {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, arg) = match s.split_once(':') {
+            Some((name, arg)) => (name, Some(arg)),
+            None => (s, None),
+        };
+        let arg = |default: &str| {
+            let arg = arg.unwrap_or(default);
+            arg.parse::<f32>().map_err(|e| format!("{:?}: {}", arg, e))
+        };
+        match name {
+            "lifetime" => Ok(Decay::Lifetime),
+            "ema" => Ok(Decay::Exponential {
+                factor: arg("0.9")?,
+            }),
+            "window" => Ok(Decay::Window {
+                generations: arg("20")? as usize,
+            }),
+            "age" => Ok(Decay::AgeDiscount {
+                half_life: arg("10")?,
+            }),
+            _ => Err(format!("unknown decay {:?}", s)),
+        }
+    }
+}
+
+/// How `GenePool::build` picks among the alleles of a gene.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Selection {
+    /// Highest average score.
+    Greedy,
+    /// Highest upper confidence bound `avg + c * sqrt(ln(N) / n)`.
+    Ucb1 { c: f32 },
+    /// Highest sample from a normal approximation of each average.
+    Thompson,
+    /// Random, weighted by `exp(avg / temperature)`.
+    Softmax { temperature: f32 },
+    /// Highest average among `size` random alleles.
+    Tournament { size: usize },
+}
+
+impl Default for Selection {
+    fn default() -> Self {
+        Selection::Greedy
+    }
+}
+
+/// Parses `greedy`, `ucb1:<c>`, `thompson`, `softmax:<temperature>` or
+/// `tournament:<size>`.
+impl FromStr for Selection {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, arg) = match s.split_once(':') {
+            Some((name, arg)) => (name, Some(arg)),
+            None => (s, None),
+        };
+        let arg = |default: &str| {
+            let arg = arg.unwrap_or(default);
+            arg.parse::<f32>().map_err(|e| format!("{:?}: {}", arg, e))
+        };
+        match name {
+            "greedy" => Ok(Selection::Greedy),
+            "ucb1" => Ok(Selection::Ucb1 { c: arg("1.4")? }),
+            "thompson" => Ok(Selection::Thompson),
+            "softmax" => Ok(Selection::Softmax {
+                temperature: arg("0.1")?,
+            }),
+            "tournament" => Ok(Selection::Tournament {
+                size: arg("3")? as usize,
+            }),
+            _ => Err(format!("unknown selection {:?}", s)),
+        }
+    }
+}
+
+/// Limits applied by `GenePool::prune`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PruneConfig {
+    /// Evaluations an allele needs before its score is trusted.
+    pub min_count: usize,
+    /// Alleles kept per gene.
+    pub max_alleles: usize,
+    /// Alleles kept in the whole pool.
+    pub max_total: usize,
+}
+
+impl Default for PruneConfig {
+    fn default() -> Self {
+        Self {
+            min_count: 1000,
+            max_alleles: 32,
+            max_total: 2000,
+        }
+    }
+}
+
+/// Parses `<min_count>,<max_alleles>,<max_total>`; missing fields keep their
+/// default.
+impl FromStr for PruneConfig {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut config = PruneConfig::default();
+        let fields = [
+            &mut config.min_count,
+            &mut config.max_alleles,
+            &mut config.max_total,
+        ];
+        for (field, value) in fields.into_iter().zip(s.split(',')) {
+            if !value.is_empty() {
+                *field = value.parse().map_err(|e| format!("{:?}: {}", value, e))?;
+            }
+        }
+        Ok(config)
+    }
+}
+
+/// What a call to `GenePool::prune` removed.
+#[derive(Default, Clone, Debug, Serialize, Deserialize)]
+pub struct PruneStats {
+    pub below_average: usize,
+    pub over_capacity: usize,
+    /// Genes dropped because nothing required them, and their alleles.
+    pub orphaned_genes: usize,
+    pub orphaned_alleles: usize,
+    /// Alleles left in the pool.
+    pub remaining: usize,
+}
+
+impl PruneStats {
+    pub fn pruned(&self) -> usize {
+        self.below_average + self.over_capacity + self.orphaned_alleles
+    }
+}
+
+/// Name, display colour and breeding parameters of a colour pool.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PoolConfig {
+    pub name: String,
+    /// RGB between 0 and 1.
+    pub color: [f32; 3],
+    /// Probability of mutating each genome assembled from the gene pool.
+    pub mutation_rate: f32,
+}
+
+impl PoolConfig {
+    /// Configuration of `n` pools: the first three are the historical red,
+    /// green and blue ones, the others get hues spread around the wheel.
+    pub fn defaults(n: usize) -> Vec<PoolConfig> {
+        let classic = [
+            ("red", [1.0, 0.3, 0.1], 0.01),
+            ("green", [0.3, 1.0, 0.1], 0.005),
+            ("blue", [0.2, 0.4, 1.0], 0.001),
+        ];
+        (0..n)
+            .map(|i| match classic.get(i) {
+                Some((name, color, mutation_rate)) => PoolConfig {
+                    name: name.to_string(),
+                    color: *color,
+                    mutation_rate: *mutation_rate,
+                },
+                None => PoolConfig {
+                    name: format!("pool-{}", i),
+                    color: hue(i as f32 / n as f32),
+                    mutation_rate: 0.001,
+                },
+            })
+            .collect()
+    }
+}
+
+/// Saturated colour of `hue`, between 0 and 1.
+fn hue(hue: f32) -> [f32; 3] {
+    let channel = |offset: f32| {
+        let x = ((hue + offset).fract() * 6.0 - 3.0).abs();
+        (x - 1.0).clamp(0.2, 1.0)
+    };
+    [channel(0.0), channel(2.0 / 3.0), channel(1.0 / 3.0)]
+}
+
+impl PoolConfig {
+    /// Applies `<name>[:<#rrggbb>[:<mutation rate>]]`, empty or missing
+    /// fields keeping their value.
+    pub fn update(&mut self, spec: &str) -> Result<(), String> {
+        let mut fields = spec.split(':');
+        if let Some(name) = fields.next().filter(|n| !n.is_empty()) {
+            self.name = name.to_string();
+        }
+        if let Some(color) = fields.next().filter(|c| !c.is_empty()) {
+            let hex = color.strip_prefix('#').unwrap_or(color);
+            let rgb = u32::from_str_radix(hex, 16)
+                .ok()
+                .filter(|_| hex.len() == 6)
+                .ok_or_else(|| format!("{:?} is not a #rrggbb colour", color))?;
+            self.color = [
+                (rgb >> 16 & 0xff) as f32 / 255.0,
+                (rgb >> 8 & 0xff) as f32 / 255.0,
+                (rgb & 0xff) as f32 / 255.0,
+            ];
+        }
+        if let Some(rate) = fields.next().filter(|r| !r.is_empty()) {
+            self.mutation_rate = rate.parse().map_err(|e| format!("{:?}: {}", rate, e))?;
+        }
+        Ok(())
     }
 }
 
@@ -21,19 +398,40 @@ pub type AlleleID = u64;
 pub trait Allele<G: Hash + Eq + Serialize + Clone> {
     fn get_allele_id(&self) -> AlleleID;
     fn get_gene_requirements(&self) -> Vec<G>;
+    /// Stops depending on `gene`.
+    fn drop_requirement(&mut self, gene: &G);
 }
 
 #[derive(Default, Clone, Serialize, Deserialize)]
 pub struct GenePool<G: Hash + Eq + Serialize + Clone, A: Allele<G> + Serialize + Clone> {
     pub genes: HashMap<G, HashMap<AlleleID, (A, Score)>>,
+    pub selection: Selection,
+    pub decay: Decay,
+    /// Generation scores are currently recorded for.
+    pub generation: usize,
 }
 
 impl<G: Hash + Eq + Serialize + Clone, A: Allele<G> + Serialize + Clone> GenePool<G, A> {
     pub fn new() -> Self {
         Self {
             genes: HashMap::new(),
+            selection: Default::default(),
+            decay: Default::default(),
+            generation: 0,
         }
     }
+    /// Moves to `generation`, ageing every score by the pool's decay.
+    pub fn advance(&mut self, generation: usize) {
+        if generation == self.generation {
+            return;
+        }
+        for alleles in self.genes.values_mut() {
+            for (_, score) in alleles.values_mut() {
+                score.age(&self.decay, self.generation, generation);
+            }
+        }
+        self.generation = generation;
+    }
     pub fn record(&mut self, gene: &G, allele: &A, fitness: f32) {
         if !self.genes.contains_key(&gene) {
             self.genes.insert(gene.clone(), HashMap::new());
@@ -45,7 +443,7 @@ impl<G: Hash + Eq + Serialize + Clone, A: Allele<G> + Serialize + Clone> GenePoo
         }
 
         let (_, score) = pool.get_mut(&allele_id).unwrap();
-        score.record(fitness);
+        score.record(fitness, self.generation);
 
         // if pool.len() > 10 {
         //     let min_use = 1000;
@@ -60,50 +458,242 @@ impl<G: Hash + Eq + Serialize + Clone, A: Allele<G> + Serialize + Clone> GenePoo
         //     }
         // }
     }
+    /// Records `allele` with the best average found for `gene`, so it is
+    /// competitive until it gets evaluated.
+    pub fn seed(&mut self, gene: &G, allele: &A) {
+        let best = self
+            .genes
+            .get(gene)
+            .and_then(|alleles| {
+                self.decay
+                    .values(alleles, self.generation)
+                    .into_values()
+                    .max_by(|a, b| a.partial_cmp(b).unwrap_or(Ordering::Equal))
+            })
+            .unwrap_or(0.0);
+        self.record(gene, allele, best);
+    }
     pub fn get_genes(&self) -> Vec<G> {
         self.genes.keys().cloned().collect()
     }
-    pub fn build(&self, genes: Vec<G>) -> HashMap<G, A> {
+    /// Picks an allele for each of `genes` and for up to `limit` of the genes
+    /// they require. Requirements that are missing from the pool or over the
+    /// limit are dropped from the alleles that have them, so every allele
+    /// returned only depends on genes that are returned too.
+    pub fn build(&self, genes: Vec<G>, limit: usize) -> HashMap<G, A> {
         let mut ret = HashMap::new();
-        for gene in genes {
-            self.require(&gene, &mut ret);
+        for gene in &genes {
+            if let Some(alleles) = self.genes.get(gene).filter(|a| !a.is_empty()) {
+                ret.insert(gene.clone(), self.select(alleles).clone());
+            }
+        }
+        let limit = ret.len().saturating_add(limit);
+        for gene in &genes {
+            let requirements = ret
+                .get(gene)
+                .map(|allele| allele.get_gene_requirements())
+                .unwrap_or_default();
+            for req in requirements {
+                self.require(&req, &mut ret, limit);
+            }
+        }
+        let missing: Vec<(G, G)> = ret
+            .iter()
+            .flat_map(|(gene, allele)| {
+                allele
+                    .get_gene_requirements()
+                    .into_iter()
+                    .filter(|req| !ret.contains_key(req))
+                    .map(move |req| (gene.clone(), req))
+            })
+            .collect();
+        for (gene, req) in missing {
+            ret.get_mut(&gene).unwrap().drop_requirement(&req);
         }
         ret
     }
-    pub fn prune(&mut self) {
-        // let dipendenze = HashMap::new();
+    /// Drops weak alleles and genes nothing depends on anymore. Alleles are
+    /// ranked by the value the decay gives them. Alleles with fewer than
+    /// `min_count` evaluations are only dropped when a cap cannot be met
+    /// otherwise, and a gene never loses its last allele, so the genes
+    /// required by the surviving alleles can always be built.
+    pub fn prune(&mut self, config: &PruneConfig, is_root: impl Fn(&G) -> bool) -> PruneStats {
+        let mut stats = PruneStats::default();
+        let (decay, generation) = (self.decay, self.generation);
 
-        // self.genes.values().for_each(|alleles| {
-        //     alleles.values().for_each(|(allele, _)| {
-        //         allele.get_gene_requirements().iter().for_each(|req| {
-        //             req
-        //         });
-        //     });
-        // });
+        for alleles in self.genes.values_mut() {
+            // Below the average of the evaluated alleles.
+            let values = decay.values(alleles, generation);
+            let evaluated: Vec<f32> = alleles
+                .iter()
+                .filter(|(_, (_, s))| s.count >= config.min_count)
+                .map(|(id, _)| values[id])
+                .collect();
+            if evaluated.len() >= 3 {
+                let avg = evaluated.iter().sum::<f32>() / evaluated.len() as f32;
+                let before = alleles.len();
+                alleles.retain(|id, (_, s)| s.count < config.min_count || values[id] >= avg);
+                stats.below_average += before - alleles.len();
+            }
 
-        self.genes.iter_mut().for_each(|(_, alleles)| {
-            if alleles.len() < 3 {
-                return;
+            // Over the per-gene cap.
+            if alleles.len() > config.max_alleles.max(1) {
+                let excess = alleles.len() - config.max_alleles.max(1);
+                let values = decay.values(alleles, generation);
+                for id in Self::weakest(alleles, &values, config.min_count, excess) {
+                    alleles.remove(&id);
+                    stats.over_capacity += 1;
+                }
             }
-            let avg: f32 = alleles.values().map(|(_, s)| s.avg).sum::<f32>() / alleles.len() as f32;
-            let indexes = alleles
-                .values()
-                .filter_map(|(a, s)| {
-                    if s.avg < avg {
-                        Some(a.get_allele_id())
-                    } else {
-                        None
+        }
+
+        // Over the pool cap: evict the weakest alleles across all genes,
+        // keeping the best one of each.
+        let total = self.genes.values().map(|a| a.len()).sum::<usize>();
+        if total > config.max_total {
+            let mut candidates: Vec<(bool, f32, G, AlleleID)> = vec![];
+            for (gene, alleles) in &self.genes {
+                let values = decay.values(alleles, generation);
+                let best = values
+                    .iter()
+                    .max_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap_or(Ordering::Equal))
+                    .map(|(id, _)| *id);
+                for (id, (_, s)) in alleles {
+                    if Some(*id) != best {
+                        candidates.push((
+                            s.count >= config.min_count,
+                            values[id],
+                            gene.clone(),
+                            *id,
+                        ));
                     }
-                })
-                .collect::<Vec<_>>();
-            indexes.iter().for_each(|id| {
-                alleles.remove(&id);
+                }
+            }
+            candidates.sort_by(|a, b| {
+                b.0.cmp(&a.0)
+                    .then(a.1.partial_cmp(&b.1).unwrap_or(Ordering::Equal))
             });
-            // alleles.iter().for_each()
+            for (_, _, gene, id) in candidates.into_iter().take(total - config.max_total) {
+                self.genes.get_mut(&gene).unwrap().remove(&id);
+                stats.over_capacity += 1;
+            }
+        }
+
+        // Genes that are not roots and no allele requires anymore can never be
+        // built again. Dropping them may orphan the genes they required.
+        loop {
+            let required: HashSet<G> = self
+                .genes
+                .values()
+                .flat_map(|alleles| alleles.values())
+                .flat_map(|(allele, _)| allele.get_gene_requirements())
+                .collect();
+            let orphans: Vec<G> = self
+                .genes
+                .keys()
+                .filter(|gene| !is_root(gene) && !required.contains(gene))
+                .cloned()
+                .collect();
+            if orphans.is_empty() {
+                break;
+            }
+            for gene in orphans {
+                stats.orphaned_alleles += self.genes.remove(&gene).map_or(0, |a| a.len());
+                stats.orphaned_genes += 1;
+            }
+        }
+
+        stats.remaining = self.genes.values().map(|a| a.len()).sum();
+        stats
+    }
+    /// IDs of the `n` weakest alleles, never all of them: the evaluated ones
+    /// by value first, then the least evaluated.
+    fn weakest(
+        alleles: &HashMap<AlleleID, (A, Score)>,
+        values: &HashMap<AlleleID, f32>,
+        min_count: usize,
+        n: usize,
+    ) -> Vec<AlleleID> {
+        let mut ranked: Vec<(&AlleleID, &Score)> =
+            alleles.iter().map(|(id, (_, s))| (id, s)).collect();
+        ranked.sort_by(|(a_id, a), (b_id, b)| {
+            (b.count >= min_count)
+                .cmp(&(a.count >= min_count))
+                .then(
+                    values[*a_id]
+                        .partial_cmp(&values[*b_id])
+                        .unwrap_or(Ordering::Equal),
+                )
+                .then(a.count.cmp(&b.count))
         });
+        ranked
+            .into_iter()
+            .take(n.min(alleles.len().saturating_sub(1)))
+            .map(|(id, _)| *id)
+            .collect()
     }
-    fn require(&self, gene: &G, ret: &mut HashMap<G, A>) {
-        if ret.contains_key(gene) {
+    fn select<'a>(&self, alleles: &'a HashMap<AlleleID, (A, Score)>) -> &'a A {
+        let mean = alleles.values().map(|(_, s)| s.avg).sum::<f32>() / alleles.len().max(1) as f32;
+        let alleles: Vec<&(A, Score)> = alleles.values().collect();
+        let value = |s: &Score| self.decay.value(s, self.generation, mean);
+        let best_by = |value: &dyn Fn(&Score) -> f32, candidates: &[&'a (A, Score)]| {
+            candidates
+                .iter()
+                .map(|entry| {
+                    let (allele, score): &'a (A, Score) = *entry;
+                    (allele, value(score))
+                })
+                .max_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap_or(Ordering::Equal))
+                .unwrap()
+                .0
+        };
+        match self.selection {
+            Selection::Greedy => best_by(&value, &alleles),
+            Selection::Ucb1 { c } => {
+                let total = alleles.iter().map(|(_, s)| s.weight).sum::<f32>().max(1.0);
+                best_by(
+                    &|s| {
+                        if s.weight <= 0.0 {
+                            f32::INFINITY
+                        } else {
+                            value(s) + c * (total.ln() / s.weight).sqrt()
+                        }
+                    },
+                    &alleles,
+                )
+            }
+            Selection::Thompson => best_by(
+                &|s| value(s) + rand_normal() * s.std() / s.weight.max(1.0).sqrt(),
+                &alleles,
+            ),
+            Selection::Softmax { temperature } => {
+                let max = alleles
+                    .iter()
+                    .map(|(_, s)| value(s))
+                    .fold(f32::MIN, f32::max);
+                let weights: Vec<f32> = alleles
+                    .iter()
+                    .map(|(_, s)| ((value(s) - max) / temperature.max(1e-6)).exp())
+                    .collect();
+                let mut x = random::<f32>() * weights.iter().sum::<f32>();
+                for ((a, _), w) in alleles.iter().zip(&weights) {
+                    x -= w;
+                    if x <= 0.0 {
+                        return a;
+                    }
+                }
+                &alleles.last().unwrap().0
+            }
+            Selection::Tournament { size } => {
+                let candidates: Vec<_> = (0..size.max(1))
+                    .map(|_| alleles[random::<usize>() % alleles.len()])
+                    .collect();
+                best_by(&value, &candidates)
+            }
+        }
+    }
+    fn require(&self, gene: &G, ret: &mut HashMap<G, A>, limit: usize) {
+        if ret.contains_key(gene) || ret.len() >= limit {
             return;
         }
         let alleles = self.genes.get(gene);
@@ -116,21 +706,10 @@ impl<G: Hash + Eq + Serialize + Clone, A: Allele<G> + Serialize + Clone> GenePoo
             return;
         }
 
-        let allele = alleles
-            .values()
-            .max_by(|(_a, a_score), (_b, b_score)| {
-                if a_score.avg > b_score.avg {
-                    Ordering::Greater
-                } else {
-                    Ordering::Less
-                }
-            })
-            .unwrap()
-            .0
-            .clone();
+        let allele = self.select(alleles).clone();
         ret.insert(gene.clone(), allele.clone());
         for req in allele.get_gene_requirements() {
-            self.require(&req, ret)
+            self.require(&req, ret, limit)
         }
     }
 }
@@ -160,7 +160,16 @@ impl Widget<MyData> for EvoWidget {
         } else {
             ctx.fill(bounds, &Color::BLACK);
         }
+        let world = &data.server.sim.world;
+        let s = bounds.width() as f64 / world.width as f64;
+        for (x, y) in &world.obstacles {
+            let (x0, y0) = (s * *x as f64, s * *y as f64);
+            ctx.fill(Rect::new(x0, y0, x0 + s, y0 + s), &Color::grey(0.5));
+        }
         data.server.sim.replicants.iter().for_each(|rep| {
+            if rep.caught.is_some() {
+                return;
+            }
             let s = bounds.width() as f64 / data.server.sim.world.width as f64;
             let x0 = s * rep.pos.0 as f64;
             let x1 = x0 + s;
@@ -168,27 +177,27 @@ impl Widget<MyData> for EvoWidget {
             let y1 = y0 + s;
             let is_alive = rep.is_alive(&data.server.sim.world, &data.server.sim.mapper);
             // let h = rep.net.links().count() * 30 % 360;
-            let c = rep.net.color;
-            let max = c[0].max(c[1]).max(c[2]) as f64;
-            let min = c[0].min(c[1]).min(c[2]) as f64;
+            let c = &rep.net.color;
+            let pools = &data.server.pools;
+            let max = c.iter().cloned().fold(f32::MIN, f32::max) as f64;
+            let min = c.iter().cloned().fold(f32::MAX, f32::min) as f64;
             let diff = (max - min).max(0.01);
             let base = 0.2;
             let opacity = if is_alive { 1.0 } else { 0.4 };
             let color = if data.highlight_pools {
-                if rep.net.pool() == 0 {
-                    Color::rgba(1.0, 0.3, 0.1, opacity)
-                } else if rep.net.pool() == 1 {
-                    Color::rgba(0.3, 1.0, 0.1, opacity)
-                } else {
-                    Color::rgba(0.2, 0.4, 1.0, opacity)
-                }
+                let [r, g, b] = pools[rep.net.pool().min(pools.len() - 1)].color;
+                Color::rgba(r as f64, g as f64, b as f64, opacity)
             } else {
-                Color::rgba(
-                    base + (1.0 - base) * (-min + (c[0] as f64) / diff),
-                    base + (1.0 - base) * (-min + (c[1] as f64) / diff),
-                    base + (1.0 - base) * (-min + (c[2] as f64) / diff),
-                    opacity,
-                )
+                // Display colours of the pools, weighted by the affinity to
+                // each of them.
+                let mut rgb = [0.0; 3];
+                for (channel, pool) in c.iter().zip(pools) {
+                    let weight = base + (1.0 - base) * (-min + (*channel as f64) / diff);
+                    for (x, p) in rgb.iter_mut().zip(pool.color) {
+                        *x += weight * p as f64;
+                    }
+                }
+                Color::rgba(rgb[0].min(1.0), rgb[1].min(1.0), rgb[2].min(1.0), opacity)
             };
             // let color = if is_alive {
             //     let base = 0.2;
@@ -1,10 +1,12 @@
+use std::collections::HashSet;
+
 use serde::{Deserialize, Serialize};
 
 use crate::{
     genome::HasGenome,
-    net::{Net, NetGenome},
+    net::{self, Net, NetGenome},
     simulation::CellMapper,
-    world::World,
+    world::{SurvivalRule, World},
 };
 
 #[derive(Default, Clone, Debug, Serialize, Deserialize)]
@@ -13,6 +15,21 @@ pub struct Replicant {
     pub net: Net,
     pub time: usize,
     pub moves: usize,
+    /// Ticks spent alive during the round.
+    pub alive_ticks: usize,
+    /// Checkpoints of the round at which the replicant was alive.
+    pub checkpoints_alive: usize,
+    /// Cells occupied at least once during the round.
+    pub visited: HashSet<(i32, i32)>,
+    /// Times each action fired during the round, indexed like `Action`.
+    pub actions: [usize; 4],
+    /// Ticks spent next to a member of the opposing pool of the scenario.
+    pub contacts: usize,
+    /// Tick at which a predator caught the replicant, which then stays out
+    /// of the world until the round ends.
+    pub caught: Option<usize>,
+    /// Prey caught during the round.
+    pub captures: usize,
 }
 
 impl Replicant {
@@ -20,179 +37,47 @@ impl Replicant {
         (self.pos.0 - rep.pos.0).abs() + (self.pos.1 - rep.pos.1).abs()
     }
     pub fn is_alive(&self, world: &World, map: &CellMapper) -> bool {
-        // return true;
-        let x = self.pos.0;
-        let y = self.pos.1;
+        if self.caught.is_some() {
+            return false;
+        }
         let px = self.pos.0 as f32;
         let py = self.pos.1 as f32;
         let w = world.width as f32;
         let h = world.height as f32;
-        return self.pos.0 > world.width/2;
-        // || self.pos.0 < world.width/4;
-
-        // if x >= world.width-1 || x <=0 {
-        //     return false;
-        // }
-        // if y >= world.height-1 || y <=0 {
-        //     return false;
-        // }
-        // if px > w * 0.95 {
-        //     return false;
-        // }
-        // if px < w * 0.05 {
-        //     return false;
-        // }
-        // if py > h * 0.95 {
-        //     return false;
-        // }
-        // if py < h * 0.05 {
-        //     return false;
-        // }
-
+        match &world.survival {
+            SurvivalRule::Zone { x } => px > w * x,
+            SurvivalRule::Region { x, y } => {
+                px >= w * x.0 && px < w * x.1 && py >= h * y.0 && py < h * y.1
+            }
+            SurvivalRule::Crowd => self.crowded(map),
+        }
+    }
+    fn crowded(&self, map: &CellMapper) -> bool {
+        let x = self.pos.0;
+        let y = self.pos.1;
         let friend = self.net.pool();
-        let ally = (friend + 1) % 3;
-        let enemy = (friend + 2) % 3;
-        let green = 1;
-        let blue = 2;
-        // if pool == 1 {
-        //     return map.isexcept(x-1, y, pool) as i8
-        //         + map.isexcept(x+1, y, pool) as i8
-        //         + map.isexcept(x, y+1, pool) as i8
-        //         + map.isexcept(x, y-1, pool) as i8
-        //         > 2;
-        // }
-        // let empty_count = !map.has(x - 1, y) as i8
-        //     + !map.has(x + 1, y) as i8
-        //     + !map.has(x, y + 1) as i8
-        //     + !map.has(x, y - 1) as i8;
-        // let friend_count = map.is(x - 1, y, pool) as i8
-        //     + map.is(x + 1, y, pool) as i8
-        //     + map.is(x, y + 1, pool) as i8
-        //     + map.is(x, y - 1, pool) as i8;
-        // let ally_count = map.is(x - 1, y, (pool + 1) % 3) as i8
-        //     + map.is(x + 1, y, (pool + 1) % 3) as i8
-        //     + map.is(x, y + 1, (pool + 1) % 3) as i8
-        //     + map.is(x, y - 1, (pool + 1) % 3) as i8;
-        let enemy_count = map.is(x - 1, y, enemy) as i8
-            + map.is(x + 1, y, enemy) as i8
-            + map.is(x, y + 1, enemy) as i8
-            + map.is(x - 1, y + 1, enemy) as i8
-            + map.is(x + 1, y + 1, enemy) as i8
-            + map.is(x, y - 1, enemy) as i8
-            + map.is(x - 1, y - 1, enemy) as i8
-            + map.is(x + 1, y - 1, enemy) as i8;
-        let friend_count = map.is(x - 1, y, friend) as i8
-            + map.is(x + 1, y, friend) as i8
-            + map.is(x, y + 1, friend) as i8
-            + map.is(x - 1, y + 1, friend) as i8
-            + map.is(x + 1, y + 1, friend) as i8
-            + map.is(x, y - 1, friend) as i8
-            + map.is(x - 1, y - 1, friend) as i8
-            + map.is(x + 1, y - 1, friend) as i8;
-
-        let ally_count = map.is(x - 1, y, ally) as i8
-            + map.is(x + 1, y, ally) as i8
-            + map.is(x, y + 1, ally) as i8
-            + map.is(x - 1, y + 1, ally) as i8
-            + map.is(x + 1, y + 1, ally) as i8
-            + map.is(x, y - 1, ally) as i8
-            + map.is(x - 1, y - 1, ally) as i8
-            + map.is(x + 1, y - 1, ally) as i8;
-        let green_count = map.is(x - 1, y, green) as i8
-            + map.is(x + 1, y, green) as i8
-            + map.is(x, y + 1, green) as i8
-            + map.is(x - 1, y + 1, green) as i8
-            + map.is(x + 1, y + 1, green) as i8
-            + map.is(x, y - 1, green) as i8
-            + map.is(x - 1, y - 1, green) as i8
-            + map.is(x + 1, y - 1, green) as i8;
-        let blue_count = map.is(x - 1, y, blue) as i8
-            + map.is(x + 1, y, blue) as i8
-            + map.is(x, y + 1, blue) as i8
-            + map.is(x - 1, y + 1, blue) as i8
-            + map.is(x + 1, y + 1, blue) as i8
-            + map.is(x, y - 1, blue) as i8
-            + map.is(x - 1, y - 1, blue) as i8
-            + map.is(x + 1, y - 1, blue) as i8;
-        let any_count = map.has(x - 1, y) as i8
-            + map.has(x + 1, y) as i8
-            + map.has(x, y + 1) as i8
-            + map.has(x - 1, y + 1) as i8
-            + map.has(x + 1, y + 1) as i8
-            + map.has(x, y - 1) as i8
-            + map.has(x - 1, y - 1) as i8
-            + map.has(x + 1, y - 1) as i8;
-        let empty_count = !map.has(x - 1, y) as i8
-            + !map.has(x + 1, y) as i8
-            + !map.has(x, y + 1) as i8
-            + !map.has(x - 1, y + 1) as i8
-            + !map.has(x + 1, y + 1) as i8
-            + !map.has(x, y - 1) as i8
-            + !map.has(x - 1, y - 1) as i8
-            + !map.has(x + 1, y - 1) as i8;
-        // return friend_count + ally_count - enemy_count > 5;
+        let count = |pool: usize| {
+            [
+                (-1, 0),
+                (1, 0),
+                (0, 1),
+                (-1, 1),
+                (1, 1),
+                (0, -1),
+                (-1, -1),
+                (1, -1),
+            ]
+            .iter()
+            .filter(|(dx, dy)| map.is(x + dx, y + dy, pool))
+            .count()
+        };
+        // Pool 0 needs room away from the others, the others need company.
+        let others: usize = (1..net::pools()).map(count).sum();
         if friend == 0 {
-            return ally_count + enemy_count < 2;
+            others < 2
         } else {
-            return green_count + blue_count > 6;
+            others > 6
         }
-        // return map.is(x, y - 1, friend);
-
-        // let has_friend_x = map.is(x - 1, y, pool) || map.is(x + 1, y, pool);
-        // let has_friend_y = map.is(x, y + 1, pool) || map.is(x, y - 1, pool);
-        // let has_friend = has_friend_x || has_friend_y;
-        // let has_enemy_x = (map.has(x - 1, y) && !map.is(x - 1, y, pool))
-        // || (map.has(x + 1, y) && !map.is(x + 1, y, pool));
-        // let has_enemy_y = (map.has(x, y + 1) && !map.is(x, y + 1, pool))
-        // || (map.has(x, y - 1) && !map.is(x, y - 1, pool));
-        // let has_enemy = has_enemy_x || has_enemy_y;
-        // // return has_enemy_x && has_friend_y;
-        // return has_friend && !has_enemy;
-
-        // return map.is(x - 1, y, pool);
-        // if self.net.pool() > 0 {
-        //     return
-        //         !map.has(x-1, y)
-        //     && !map.has(x+1, y)
-        //     && !map.has(x, y+1)
-        //     && !map.has(x, y-1)
-        // } else {
-        //     return
-        //         map.has(x-1, y)
-        //     || map.has(x+1, y)
-        //     || map.has(x, y+1)
-        //     || map.has(x, y-1)
-        // }
-        // ;
-        // return
-        //  !map.has(x+1, y)
-        //  && !map.has(x-1, y)
-        //  && map.has(x, y+1)
-        //  && !map.has(x, y-1)
-        // ;
-        // return true;
-        // return
-        //  map.has(x, y-1)
-        //  && !map.has(x, y+1);
-        // return (map.has(x + 1, y) == map.has(x - 1, y) && map.has(x, y + 1) != map.has(x, y - 1))
-        //     || (map.has(x + 1, y) != map.has(x - 1, y) && map.has(x, y + 1) == map.has(x, y - 1));
-        // let p = self.pos.0 as f32;
-        // (-3.14*0.2 + p*0.6).sin() > 0.05
-        // px > (world.width as f32)*0.2
-        // && px < (world.width as f32)*0.8
-        // && py > (world.height as f32)*0.2
-        // && py < (world.height as f32)*0.8
-        // py < world.height as f32 * 0.1
-        //     || (px > world.width as f32 * 0.8 && py > world.height as f32 * 0.8)
-        // || px < world.width/10
-        // ((px / 30) % 2 == 0)
-        // && ((p.1 / 30) % 2 == 0)
-        // && px.min(p.1) > 20
-        // && px.max(p.1) < world.width-20
-        // px > world.width / 4
-        //     && px < world.width / 4 * 3
-        //     && p.1 > world.height / 4
-        //     && p.1 < world.height / 4 * 3
     }
 }
 impl HasGenome<NetGenome> for Replicant {
@@ -0,0 +1,277 @@
+use std::{
+    cmp::Ordering,
+    collections::{HashMap, HashSet},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    coevolution::ArmsRace,
+    net::{NeuralNode, NeuralTarget},
+    nsga::ObjectiveStats,
+    pool::{AlleleID, GenePool, PruneStats, Score},
+    schedule::Change,
+    server::Server,
+};
+
+/// Upper bounds of the allele frequency bins of `PoolReport::frequency`.
+pub const FREQUENCY_BINS: [f32; 5] = [0.01, 0.05, 0.2, 0.5, 1.0];
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct AlleleReport {
+    pub id: AlleleID,
+    pub count: usize,
+    /// Share of the gene's evaluations.
+    pub frequency: f32,
+    pub avg: f32,
+    /// 95% confidence interval of the average.
+    pub low: f32,
+    pub high: f32,
+    pub first_seen: usize,
+    pub last_seen: usize,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct GeneReport {
+    pub gene: String,
+    pub alleles: usize,
+    pub evaluations: usize,
+    pub effective_alleles: f32,
+    /// Allele with the highest average.
+    pub best: AlleleReport,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct PoolReport {
+    pub pool: usize,
+    #[serde(default)]
+    pub name: String,
+    pub genes: usize,
+    pub alleles: usize,
+    pub evaluations: usize,
+    /// Mean over the genes of `1 / sum(p^2)`, with `p` the allele
+    /// frequencies: 1 for a fixed gene, the number of alleles when they are
+    /// used evenly.
+    pub effective_alleles: f32,
+    /// Alleles per frequency bin, see `FREQUENCY_BINS`.
+    pub frequency: [usize; 5],
+    /// Most evaluated genes first.
+    pub top: Vec<GeneReport>,
+}
+
+/// One line of the metrics file written next to the save every generation.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Metrics {
+    pub generation: usize,
+    /// Survivors of each pool relative to an even split.
+    pub fractions: Vec<f32>,
+    pub pools: Vec<PoolReport>,
+    pub pruned: HashMap<usize, PruneStats>,
+    /// Objectives of a multi-objective strategy.
+    pub objectives: Vec<ObjectiveStats>,
+    /// Cross-evaluation against past opponents of a co-evolution scenario.
+    pub arms_race: Vec<ArmsRace>,
+    /// Changes made to the world for the next generation.
+    pub changes: Vec<Change>,
+}
+
+pub fn gene_name(gene: &NeuralTarget) -> String {
+    match gene {
+        NeuralTarget::Hidden(id) => id.to_string(),
+        NeuralTarget::Action(action) => format!("{:?}", action),
+    }
+}
+
+fn allele(id: AlleleID, score: &Score, evaluations: usize) -> AlleleReport {
+    let margin = 1.96 * score.std() / score.weight().max(1.0).sqrt();
+    AlleleReport {
+        id,
+        count: score.count(),
+        frequency: score.count() as f32 / evaluations.max(1) as f32,
+        avg: score.avg(),
+        low: score.avg() - margin,
+        high: score.avg() + margin,
+        first_seen: score.first_seen(),
+        last_seen: score.last_seen(),
+    }
+}
+
+pub fn report(pool_i: usize, pool: &GenePool<NeuralTarget, NeuralNode>, top: usize) -> PoolReport {
+    let mut report = PoolReport {
+        pool: pool_i,
+        name: String::new(),
+        genes: pool.genes.len(),
+        alleles: 0,
+        evaluations: 0,
+        effective_alleles: 0.0,
+        frequency: [0; 5],
+        top: vec![],
+    };
+    let mut genes = vec![];
+    for (gene, alleles) in &pool.genes {
+        if alleles.is_empty() {
+            continue;
+        }
+        let evaluations: usize = alleles.values().map(|(_, s)| s.count()).sum();
+        let mut homozygosity = 0.0;
+        for (_, score) in alleles.values() {
+            let p = score.count() as f32 / evaluations.max(1) as f32;
+            homozygosity += p * p;
+            let bin = FREQUENCY_BINS.iter().position(|max| p < *max).unwrap_or(4);
+            report.frequency[bin] += 1;
+        }
+        let (id, (_, best)) = alleles
+            .iter()
+            .max_by(|(_, (_, a)), (_, (_, b))| {
+                a.avg().partial_cmp(&b.avg()).unwrap_or(Ordering::Equal)
+            })
+            .unwrap();
+        let effective_alleles = if homozygosity > 0.0 {
+            1.0 / homozygosity
+        } else {
+            alleles.len() as f32
+        };
+        report.alleles += alleles.len();
+        report.evaluations += evaluations;
+        report.effective_alleles += effective_alleles;
+        genes.push(GeneReport {
+            gene: gene_name(gene),
+            alleles: alleles.len(),
+            evaluations,
+            effective_alleles,
+            best: allele(*id, best, evaluations),
+        });
+    }
+    if !genes.is_empty() {
+        report.effective_alleles /= genes.len() as f32;
+    }
+    genes.sort_by(|a, b| b.evaluations.cmp(&a.evaluations).then(a.gene.cmp(&b.gene)));
+    genes.truncate(top);
+    report.top = genes;
+    report
+}
+
+pub fn reports(server: &Server, top: usize) -> Vec<PoolReport> {
+    let mut reports: Vec<_> = server
+        .gene_pools
+        .iter()
+        .map(|(i, pool)| {
+            let mut report = report(*i, pool, top);
+            report.name = server.pool_name(*i);
+            report
+        })
+        .collect();
+    reports.sort_by_key(|r| r.pool);
+    reports
+}
+
+impl PoolReport {
+    pub fn print(&self) {
+        println!(
+            "pool {} {}: {} genes, {} alleles, {} evaluations, {:.2} effective alleles per gene",
+            self.pool,
+            self.name,
+            self.genes,
+            self.alleles,
+            self.evaluations,
+            self.effective_alleles
+        );
+        let mut low = 0.0;
+        for (count, high) in self.frequency.iter().zip(FREQUENCY_BINS) {
+            println!(
+                "  frequency {:>4.0}%-{:>3.0}%: {}",
+                low * 100.0,
+                high * 100.0,
+                count
+            );
+            low = high;
+        }
+        println!("  gene alleles evaluations effective best avg [95% ci] seen");
+        for gene in &self.top {
+            println!(
+                "  {} {} {} {:.2} {:016x} {:.3} [{:.3}, {:.3}] {}-{}",
+                gene.gene,
+                gene.alleles,
+                gene.evaluations,
+                gene.effective_alleles,
+                gene.best.id,
+                gene.best.avg,
+                gene.best.low,
+                gene.best.high,
+                gene.best.first_seen,
+                gene.best.last_seen
+            );
+        }
+    }
+}
+
+/// Prints how the gene pools of `new` differ from the ones of `old`.
+pub fn print_diff(old: &Server, new: &Server, pool: Option<usize>) {
+    println!("generation {} -> {}", old.generation, new.generation);
+    let mut pools: Vec<usize> = old
+        .gene_pools
+        .keys()
+        .chain(new.gene_pools.keys())
+        .cloned()
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .filter(|i| pool.map_or(true, |pool| pool == *i))
+        .collect();
+    pools.sort();
+    let empty = GenePool::new();
+    for i in pools {
+        let a = old.gene_pools.get(&i).unwrap_or(&empty);
+        let b = new.gene_pools.get(&i).unwrap_or(&empty);
+        let (ra, rb) = (report(i, a, 0), report(i, b, 0));
+        let ids = |pool: &GenePool<NeuralTarget, NeuralNode>| -> HashSet<AlleleID> {
+            pool.genes
+                .values()
+                .flat_map(|a| a.keys().cloned())
+                .collect()
+        };
+        let (ids_a, ids_b) = (ids(a), ids(b));
+        println!(
+            "pool {}: genes {} -> {}, alleles {} -> {} (+{} -{}), effective alleles {:.2} -> {:.2}",
+            i,
+            ra.genes,
+            rb.genes,
+            ra.alleles,
+            rb.alleles,
+            ids_b.difference(&ids_a).count(),
+            ids_a.difference(&ids_b).count(),
+            ra.effective_alleles,
+            rb.effective_alleles
+        );
+
+        let mut genes: Vec<&NeuralTarget> = a.genes.keys().chain(b.genes.keys()).collect();
+        genes.sort_by_key(|gene| gene_name(gene));
+        genes.dedup();
+        let best = |pool: &GenePool<NeuralTarget, NeuralNode>, gene| {
+            pool.genes.get(gene).and_then(|alleles| {
+                alleles
+                    .iter()
+                    .max_by(|(_, (_, a)), (_, (_, b))| {
+                        a.avg().partial_cmp(&b.avg()).unwrap_or(Ordering::Equal)
+                    })
+                    .map(|(id, (_, s))| (*id, s.avg()))
+            })
+        };
+        for gene in genes {
+            match (best(a, gene), best(b, gene)) {
+                (None, Some((id, avg))) => {
+                    println!("  + {} best {:016x} {:.3}", gene_name(gene), id, avg)
+                }
+                (Some(_), None) => println!("  - {}", gene_name(gene)),
+                (Some((id_a, avg_a)), Some((id_b, avg_b))) if id_a != id_b => println!(
+                    "  ~ {} best {:016x} {:.3} -> {:016x} {:.3}",
+                    gene_name(gene),
+                    id_a,
+                    avg_a,
+                    id_b,
+                    avg_b
+                ),
+                _ => {}
+            }
+        }
+    }
+}
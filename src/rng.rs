@@ -28,3 +28,10 @@ where
 pub fn rand_f32() -> f32 {
     rand::random::<f32>() * 2.0 - 1.0
 }
+
+/// Standard normal sample, by the Box-Muller transform.
+pub fn rand_normal() -> f32 {
+    let u1 = rand::random::<f32>().max(f32::MIN_POSITIVE);
+    let u2 = rand::random::<f32>();
+    (-2.0 * u1.ln()).sqrt() * (2.0 * std::f32::consts::PI * u2).cos()
+}
@@ -0,0 +1,169 @@
+use std::{fmt, str::FromStr};
+
+use rand::random;
+use serde::{Deserialize, Serialize};
+
+use crate::world::{SurvivalRule, World};
+
+/// When a scheduled change happens.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Trigger {
+    /// Once this generation is reached.
+    Generation(usize),
+    /// Once a generation, evaluated after the previous change, has this
+    /// share of the population survive.
+    Threshold(f32),
+}
+
+/// A change to the world.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Change {
+    Survival(SurvivalRule),
+    Size {
+        width: i32,
+        height: i32,
+    },
+    /// Blocks this many random cells.
+    Obstacles(usize),
+    /// Blocks the column at `x` times the width, but for a centred gap of
+    /// `gap` times the height.
+    Wall {
+        x: f32,
+        gap: f32,
+    },
+    ClearObstacles,
+}
+
+/// Parses `at:<generation>` or `when:<survival share>`.
+impl FromStr for Trigger {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            Some(("at", n)) => n
+                .parse()
+                .map(Trigger::Generation)
+                .map_err(|e| format!("{:?}: {}", n, e)),
+            Some(("when", share)) => share
+                .parse()
+                .map(Trigger::Threshold)
+                .map_err(|e| format!("{:?}: {}", share, e)),
+            _ => Err(format!("unknown trigger {:?}", s)),
+        }
+    }
+}
+
+/// Parses `size:<width>,<height>`, `obstacles:<count>`, `wall:<x>[,<gap>]`,
+/// `clear-obstacles` or a survival rule.
+impl FromStr for Change {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, arg) = s.split_once(':').unwrap_or((s, ""));
+        let numbers = || {
+            arg.split(',')
+                .map(|n| {
+                    n.trim()
+                        .parse::<f32>()
+                        .map_err(|e| format!("{:?}: {}", n, e))
+                })
+                .collect::<Result<Vec<_>, _>>()
+        };
+        match name {
+            "size" => match numbers()?[..] {
+                [width, height] if width >= 1.0 && height >= 1.0 => Ok(Change::Size {
+                    width: width as i32,
+                    height: height as i32,
+                }),
+                _ => Err("expected size:<width>,<height>".to_string()),
+            },
+            "obstacles" => arg
+                .parse()
+                .map(Change::Obstacles)
+                .map_err(|e| format!("{:?}: {}", arg, e)),
+            "wall" => match numbers()?[..] {
+                [x] => Ok(Change::Wall { x, gap: 0.2 }),
+                [x, gap] => Ok(Change::Wall { x, gap }),
+                _ => Err("expected wall:<x>[,<gap>]".to_string()),
+            },
+            "clear-obstacles" => Ok(Change::ClearObstacles),
+            _ => s.parse().map(Change::Survival),
+        }
+    }
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Change::Survival(rule) => write!(f, "survival {:?}", rule),
+            Change::Size { width, height } => write!(f, "size {}x{}", width, height),
+            Change::Obstacles(n) => write!(f, "{} obstacles", n),
+            Change::Wall { x, gap } => write!(f, "wall at {} with a {} gap", x, gap),
+            Change::ClearObstacles => write!(f, "no obstacles"),
+        }
+    }
+}
+
+impl Change {
+    pub fn apply(&self, world: &mut World) {
+        match self {
+            Change::Survival(rule) => world.survival = rule.clone(),
+            Change::Size { width, height } => {
+                world.width = *width;
+                world.height = *height;
+                world.obstacles.retain(|(x, y)| x < width && y < height);
+            }
+            Change::Obstacles(n) => {
+                for _ in 0..*n {
+                    world.obstacles.insert((
+                        random::<i32>().abs() % world.width.max(1),
+                        random::<i32>().abs() % world.height.max(1),
+                    ));
+                }
+            }
+            Change::Wall { x, gap } => {
+                let column = (world.width as f32 * x) as i32;
+                let gap = (world.height as f32 * gap) as i32;
+                let top = (world.height - gap) / 2;
+                for y in (0..world.height).filter(|y| *y < top || *y >= top + gap) {
+                    world.obstacles.insert((column, y));
+                }
+            }
+            Change::ClearObstacles => world.obstacles.clear(),
+        }
+    }
+}
+
+/// Changes applied to the world in order, each once its trigger is met.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct Schedule {
+    pub events: Vec<(Trigger, Change)>,
+    /// Index of the next event.
+    pub next: usize,
+    /// Generation the last change took effect at.
+    pub changed_at: Option<usize>,
+}
+
+impl Schedule {
+    /// Changes to make before `generation`, given the share of the
+    /// population that survived the previous one if it was evaluated.
+    pub fn due(&mut self, generation: usize, survival: Option<f32>) -> Vec<Change> {
+        let mut changes = vec![];
+        while let Some((trigger, change)) = self.events.get(self.next) {
+            let due = match trigger {
+                Trigger::Generation(n) => generation >= *n,
+                // The previous generation has to have been played with the
+                // last change.
+                Trigger::Threshold(share) => {
+                    self.changed_at.map_or(true, |at| generation > at)
+                        && survival.map_or(false, |survival| survival >= *share)
+                }
+            };
+            if !due {
+                break;
+            }
+            changes.push(change.clone());
+            self.changed_at = Some(generation);
+            self.next += 1;
+        }
+        changes
+    }
+}
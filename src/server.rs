@@ -1,15 +1,22 @@
-use std::{collections::HashMap, path::PathBuf, thread};
+use std::{collections::HashMap, fs::OpenOptions, io::Write, path::PathBuf, thread};
 
 use rand::random;
-use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    genome::{Genome, HasGenome},
-    net::{NetGenome, NeuralNode, NeuralSource, NeuralTarget},
-    pool::GenePool,
+    archive::{Champion, HallOfFame},
+    coevolution::{self, ArmsRace},
+    fitness::{Fitness, FitnessFn},
+    genome::HasGenome,
+    net::{self, HiddenIds, Inheritance, NetGenome, NeuralNode, NeuralTarget},
+    nsga,
+    parsimony::{self, Limits, Parsimony},
+    pool::{GenePool, PoolConfig, PruneConfig, PruneStats},
     replicant::Replicant,
-    simulation::Simulation,
+    report::{self, Metrics},
+    schedule::{Change, Schedule},
+    simulation::{RoundLength, Simulation},
+    strategy::{EvolutionStrategy, GenePoolAssembly, PoolMigration, Population, Strategy},
 };
 
 #[derive(Default, Clone, Serialize, Deserialize)]
@@ -20,32 +27,116 @@ pub struct Server {
     pub sim: Simulation,
     pub gene_pools: HashMap<usize, GenePool<NeuralTarget, NeuralNode>>,
     pub pop_size: usize,
-    pub prev_survival: [usize; 3],
+    pub prev_survival: Vec<usize>,
+    pub inheritance: Inheritance,
+    pub strategy: Strategy,
+    pub hidden_ids: HiddenIds,
+    /// Probability of replacing a child with its simplified genome.
+    pub simplify_rate: f32,
+    /// Genomes the first generation is bred from instead of random ones.
+    #[serde(skip)]
+    pub seeds: Vec<NetGenome>,
+    pub hall_of_fame: HallOfFame,
+    /// Fitness function of each pool, `FitnessFn::Survival` if missing.
+    pub fitness: HashMap<usize, FitnessFn>,
+    /// Gene pools are pruned after scoring when set.
+    pub pruning: Option<PruneConfig>,
+    /// What the last pruning removed from each pool.
+    pub pruned: HashMap<usize, PruneStats>,
+    pub parsimony: Parsimony,
+    pub limits: Limits,
+    /// Index of the island this server is, when part of an `Archipelago`.
+    pub island: Option<usize>,
+    /// Configuration of each colour pool, the three default ones if empty.
+    pub pools: Vec<PoolConfig>,
+    pub pool_migration: PoolMigration,
+    /// Generations between the arms race cross-evaluations of a co-evolution
+    /// scenario, 0 for none.
+    pub arms_race_interval: usize,
+    /// Changes to the world over the run.
+    pub schedule: Schedule,
+    pub round_length: RoundLength,
+    /// Ticks the current episode lasts.
+    pub length: usize,
+    /// Episodes each generation is evaluated over, fitness being averaged.
+    pub episodes: usize,
+    /// Index of the current episode of the generation.
+    pub episode: usize,
+    /// Sum of the scores of each replicant over the previous episodes.
+    pub episode_scores: Vec<f32>,
+    /// Previous episodes of the round each replicant survived.
+    pub episode_survival: Vec<f32>,
 }
+/// Archived opponents each arms race cross-evaluation plays against.
+const ARMS_RACE_SAMPLES: usize = 5;
+
 impl Server {
     pub fn setup(&mut self) {
-        self.sim.world.width = 80;
-        self.sim.world.height = 80;
-        self.sim.mapper.clip = Some((self.sim.world.width, self.sim.world.height));
-        self.sim.mapper.clip = None;
+        if self.sim.world.width == 0 || self.sim.world.height == 0 {
+            self.sim.world.width = 80;
+            self.sim.world.height = 80;
+        }
         // self.sim.world.lifespan = 100;
         self.pop_size = 3000;
+        let changes = self.schedule.due(self.generation, None);
+        self.change_world(self.generation, &changes);
+        self.sim.mapper.clip = Some((self.sim.world.width, self.sim.world.height));
+        self.sim.mapper.clip = None;
+        if self.pools.is_empty() {
+            self.pools = PoolConfig::defaults(3);
+        }
+        net::set_pools(self.pools.len());
+        net::set_hidden_ids(self.hidden_ids);
+        parsimony::set_limits(self.limits);
+        self.gene_pools
+            .values()
+            .flat_map(|pool| pool.genes.keys())
+            .chain(
+                self.sim
+                    .replicants
+                    .iter()
+                    .flat_map(|rep| rep.net.nodes.keys()),
+            )
+            .for_each(|target| {
+                if let NeuralTarget::Hidden(id) = target {
+                    net::reserve_hidden_id(*id);
+                }
+            });
         // eprintln!("[server] init {}", self.generation);
-        if self.generation == 0 {
-            for _ in 0..self.pop_size {
-                let mut genome = NetGenome::default();
-                genome.randomize();
-                genome.randomize_color();
+        if self.generation == 0 && self.sim.replicants.is_empty() {
+            for i in 0..self.pop_size {
+                let mut genome = if self.seeds.is_empty() {
+                    let mut genome = NetGenome::default();
+                    genome.randomize_color();
+                    genome.pool = genome.color_pool();
+                    genome
+                } else {
+                    self.seeds[i % self.seeds.len()].clone()
+                };
+                // Seeds are used once as they are, then as mutated copies.
+                if i >= self.seeds.len() {
+                    genome.randomize();
+                }
                 let rep = Replicant::from_genome(&genome);
                 self.sim.replicants.push(rep);
             }
         }
+        for rep in &mut self.sim.replicants {
+            rep.net.inheritance = self.inheritance;
+        }
+        self.length = self.round_length.sample(self.generation, self.episode);
+        self.sim.schedule(self.length + 1);
         self.sim.setup();
     }
 
     pub fn tick(&mut self) {
-        if self.time > 300 {
-            //} + crate::rng::random::<usize>(self.generation as u64) % 50 {
+        if self.time > self.length && self.episode + 1 < self.episodes {
+            self.next_episode();
+            self.time = 0;
+        }
+        if self.time > self.length {
+            self.record_champions();
+            self.import_pending();
             // if self.time > self.sim.world.lifespan {
             // println!("Round ended");
             if let Some(path) = self.auto_save.clone() {
@@ -53,21 +144,26 @@ impl Server {
                 thread::spawn(move || {
                     let tmp_file = format!("{}-tmp", &path.to_string_lossy());
                     let tmp_file_json = format!("{}.repl.json", &path.to_string_lossy());
+                    let tmp_file_json_min = format!("{}.repl.min.json", &path.to_string_lossy());
                     // let tmp_file_json_pool = format!("{}.pool.json", &path.to_string_lossy());
                     let ser = bincode::serialize(&clone).unwrap();
                     std::fs::write(&tmp_file, &ser).unwrap();
                     std::fs::rename(&tmp_file, &path).unwrap();
-                    let ser =
-                        serde_json::to_string_pretty(&clone.sim.replicants[0].to_genome().value())
-                            .unwrap();
+                    let genome = clone.sim.replicants[0].to_genome();
+                    let ser = genome.to_json();
                     // let pool = serde_json::to_string_pretty(&clone.gene_pools).unwrap();
                     std::fs::write(&tmp_file_json, &ser).unwrap();
+                    let ser = genome.simplified(0.0).to_json();
+                    std::fs::write(&tmp_file_json_min, &ser).unwrap();
                     // std::fs::write(&tmp_file_json_pool, &pool).unwrap();
                 });
             }
             self.finish_round();
             self.time = 0;
             self.generation += 1;
+            self.episode = 0;
+            self.episode_scores.clear();
+            self.episode_survival.clear();
         }
 
         if self.time == 0 {
@@ -82,6 +178,89 @@ impl Server {
         self.time += 1;
     }
 
+    /// Restarts the round with the same genomes, keeping the fitness of the
+    /// episode that ended.
+    fn next_episode(&mut self) {
+        let scores: Vec<f32> = self
+            .sim
+            .replicants
+            .iter()
+            .map(|rep| self.fitness(rep))
+            .collect();
+        self.episode_scores.resize(scores.len(), 0.0);
+        for (total, score) in self.episode_scores.iter_mut().zip(scores) {
+            *total += score;
+        }
+        let survival: Vec<bool> = self
+            .sim
+            .replicants
+            .iter()
+            .map(|rep| self.sim.survived(rep))
+            .collect();
+        self.episode_survival.resize(survival.len(), 0.0);
+        for (total, survived) in self.episode_survival.iter_mut().zip(survival) {
+            *total += survived as u8 as f32;
+        }
+        // Learning of the episode is forgotten, `setup` restores the
+        // inheritance.
+        self.sim.replicants = self
+            .sim
+            .replicants
+            .iter()
+            .map(|rep| {
+                let mut net = rep.net.clone();
+                net.inheritance = Inheritance::Baldwinian;
+                Replicant::from_genome(&net.to_genome())
+            })
+            .collect();
+        self.episode += 1;
+    }
+
+    /// Fitness of each replicant averaged over the episodes of the round.
+    fn fitnesses(&self) -> Vec<f32> {
+        self.sim
+            .replicants
+            .iter()
+            .enumerate()
+            .map(|(i, rep)| {
+                let previous = self.episode_scores.get(i).cloned().unwrap_or(0.0);
+                (previous + self.fitness(rep)) / (self.episode + 1) as f32
+            })
+            .collect()
+    }
+
+    /// Share of the episodes of the round each replicant survived.
+    fn survival(&self) -> Vec<f32> {
+        self.sim
+            .replicants
+            .iter()
+            .enumerate()
+            .map(|(i, rep)| {
+                let previous = self.episode_survival.get(i).cloned().unwrap_or(0.0);
+                (previous + self.sim.survived(rep) as u8 as f32) / (self.episode + 1) as f32
+            })
+            .collect()
+    }
+
+    /// `score` of each replicant, its fitness averaged over the episodes of
+    /// the round.
+    pub fn scores(&self) -> Vec<f32> {
+        self.sim
+            .replicants
+            .iter()
+            .zip(self.fitnesses())
+            .map(|(rep, fitness)| fitness - self.parsimony.penalty(&rep.net))
+            .collect()
+    }
+
+    /// Ticks until the current round is over and the next generation bred.
+    pub fn step_generation(&mut self) {
+        let generation = self.generation;
+        while self.generation == generation {
+            self.tick();
+        }
+    }
+
     fn _get_alive_dead(&self) -> HashMap<usize, (Vec<usize>, Vec<usize>)> {
         let mut ret = HashMap::new();
 
@@ -95,7 +274,7 @@ impl Server {
                 if !ret.contains_key(&pool) {
                     ret.insert(pool, (vec![], vec![]));
                 }
-                if rep.is_alive(&self.sim.world, &self.sim.mapper) {
+                if self.sim.survived(rep) {
                     ret.get_mut(&pool).unwrap().0.push(i);
                 } else {
                     ret.get_mut(&pool).unwrap().1.push(i);
@@ -103,173 +282,360 @@ impl Server {
             });
         ret
     }
-    fn get_pools(&self) -> HashMap<usize, Vec<Replicant>> {
-        let mut ret = HashMap::new();
-        let mut survivors: Vec<&Replicant> = self
-            .sim
-            .replicants
-            .par_iter()
-            .filter(|rep| rep.is_alive(&self.sim.world, &self.sim.mapper))
-            .collect();
-
-        // let avg_moves = 2 * survivors.iter().map(|a| a.moves).sum::<usize>() / survivors.len();
-
-        // let mut survivors: Vec<&Replicant> = survivors
-        //     .par_iter()
-        //     .filter(|x| x.moves <= avg_moves)
-        //     .map(|r| r.clone())
-        //     .collect();
-
-        // println!("After self.sim: {:#?}", survivors);
-
-        if !ret.contains_key(&0) {
-            ret.insert(0, vec![]);
-        }
-        if !ret.contains_key(&1) {
-            ret.insert(1, vec![]);
-        }
-        if !ret.contains_key(&2) {
-            ret.insert(2, vec![]);
-        }
-        while let Some(genome) = survivors.pop() {
-            let pool = genome.net.pool();
-            ret.get_mut(&pool).unwrap().push(genome.clone());
-        }
-        ret
-    }
-
     fn score_genes(&mut self) {
-        let pools = self.get_pools();
-        let pools = [
-            (pools.get(&0).unwrap().len() * pools.len()) as f32 / self.pop_size as f32,
-            (pools.get(&1).unwrap().len() * pools.len()) as f32 / self.pop_size as f32,
-            (pools.get(&2).unwrap().len() * pools.len()) as f32 / self.pop_size as f32,
-        ];
-        for rep in &self.sim.replicants {
+        let fractions = self.fractions();
+        for (rep, fitness) in self.sim.replicants.iter().zip(self.fitnesses()) {
             let pool = rep.net.pool();
-            let score = if rep.is_alive(&self.sim.world, &self.sim.mapper) {
-                1.0 + pools[pool]
-            } else {
-                0.0
-            };
+            // The penalty is taken after the survival bonus, so a genome
+            // costs the same in every pool.
+            let score = fitness * (1.0 + fractions.get(pool).cloned().unwrap_or(0.0))
+                - self.parsimony.penalty(&rep.net);
             if !self.gene_pools.contains_key(&pool) {
                 self.gene_pools.insert(pool, GenePool::new());
             }
             let pool = self.gene_pools.get_mut(&pool).unwrap();
+            pool.advance(self.generation);
 
-            for (source, node) in &rep.net.nodes {
+            for (source, node) in &rep.to_genome().nodes {
                 pool.record(source, node, score)
             }
         }
-        self.gene_pools.values_mut().for_each(|pool| {
-            // pool.prune();
-        })
+        if let Some(config) = &self.pruning {
+            // Actions are where assembly starts, every other gene has to be
+            // required by some allele to stay.
+            let is_root = |gene: &NeuralTarget| matches!(gene, NeuralTarget::Action(_));
+            for (i, pool) in self.gene_pools.iter_mut() {
+                let stats = pool.prune(config, is_root);
+                eprintln!(
+                    "pool {} pruned {} (below average {}, over capacity {}, orphaned genes {}), {} left",
+                    i,
+                    stats.pruned(),
+                    stats.below_average,
+                    stats.over_capacity,
+                    stats.orphaned_genes,
+                    stats.remaining
+                );
+                self.pruned.insert(*i, stats);
+            }
+        }
     }
 
-    fn finish_round(&mut self) {
-        self.score_genes();
-        self.print_pools_stats();
-        self.replace_replicants_v2();
+    /// Fitness of a replicant at the end of the round, by its pool's fitness
+    /// function.
+    pub fn fitness(&self, rep: &Replicant) -> f32 {
+        match self.fitness.get(&rep.net.pool()) {
+            Some(f) => f.score(rep, &self.sim),
+            None => FitnessFn::Survival.score(rep, &self.sim),
+        }
     }
-    fn print_pools_stats(&mut self) {
-        let pools = self.get_pools();
-        println!(
-            "{:.3} {:.3} {:.3}",
-            (pools.get(&0).unwrap().len() * pools.len()) as f32 / self.pop_size as f32,
-            (pools.get(&1).unwrap().len() * pools.len()) as f32 / self.pop_size as f32,
-            (pools.get(&2).unwrap().len() * pools.len()) as f32 / self.pop_size as f32,
-        );
+
+    /// Score of a replicant at the end of the round: its fitness less the
+    /// parsimony penalty.
+    pub fn score(&self, rep: &Replicant) -> f32 {
+        self.fitness(rep) - self.parsimony.penalty(&rep.net)
     }
-    fn replace_replicants_v2(&mut self) {
-        self.sim.replicants.clear();
-        for (pool_i, pool) in &self.gene_pools {
-            let mut inserted = 0;
-            while inserted < self.pop_size / self.gene_pools.len() {
-                let mut genome = NetGenome::default();
-                let alleles = pool.build(
-                    pool.get_genes()
-                        .into_iter()
-                        .filter(|gene| match gene {
-                            NeuralTarget::Action(_) => true,
-                            _ => false,
-                        })
-                        .collect(),
-                );
-                genome.nodes = alleles;
-                genome.color = [0.0, 0.0, 0.0];
-                *genome.color.get_mut(*pool_i).unwrap() = 1.0;
-                let pmut = if *pool_i == 0 {
-                    0.99
-                } else if *pool_i == 1 {
-                    0.995
-                } else {
-                    0.999
-                };
-                for _ in 0..200 {
-                    if random::<f32>() > pmut {
-                        genome.randomize();
-                    }
-                    let child = Replicant::from_genome(&genome);
-                    self.sim.replicants.push(child);
-                    inserted += 1;
-                }
+
+    fn record_champions(&mut self) {
+        let mut best: HashMap<usize, (f32, &Replicant)> = HashMap::new();
+        for (rep, score) in self.sim.replicants.iter().zip(self.scores()) {
+            let pool = rep.net.pool();
+            if best.get(&pool).map_or(true, |(s, _)| score > *s) {
+                best.insert(pool, (score, rep));
             }
         }
+        let mut champions: Vec<_> = best
+            .into_iter()
+            .map(|(pool, (score, rep))| Champion {
+                generation: self.generation,
+                pool,
+                score,
+                genome: rep.to_genome(),
+            })
+            .collect();
+        champions.sort_by_key(|c| c.pool);
+        champions
+            .into_iter()
+            .for_each(|c| self.hall_of_fame.record(c));
     }
-    fn _replace_replicants(&mut self) {
-        // println!("Replicants: {:#?}", self.sim.replicants);
-        let pools = self.get_pools();
 
-        let mut new_reps: Vec<Replicant> = vec![];
-        for i in 0..self.pop_size {
-            let x = i % pools.len();
+    /// Runs a round of the current population with `copies` of the members of
+    /// `pool` swapped for `genome`, and returns the mean fitness of the copies.
+    pub fn evaluate(&self, genome: &NetGenome, pool: usize, copies: usize) -> f32 {
+        let (eval, slots) = self.replay(genome, pool, copies);
+        slots
+            .iter()
+            .map(|i| eval.score(&eval.sim.replicants[*i]))
+            .sum::<f32>()
+            / slots.len().max(1) as f32
+    }
 
-            let pool = pools.get(&x).unwrap();
+    /// Runs a round of the current population with every member of
+    /// `opponent` swapped for `genome`, and returns the mean fitness of the
+    /// members of `pool`.
+    pub fn evaluate_against(&self, genome: &NetGenome, opponent: usize, pool: usize) -> f32 {
+        let (eval, _) = self.replay(genome, opponent, usize::MAX);
+        let members: Vec<_> = eval
+            .sim
+            .replicants
+            .iter()
+            .filter(|rep| rep.net.pool() == pool)
+            .collect();
+        members.iter().map(|rep| eval.score(rep)).sum::<f32>() / members.len().max(1) as f32
+    }
 
-            if !pool.is_empty() {
-                let parent_ai = random::<usize>() % pool.len();
-                let parent_a = pool.get(parent_ai).unwrap();
-                let mut genome = if random::<f32>() > 0.9 {
-                    // let parent_b = pool.get(&random() % pool.len()).unwrap();
-                    let mut parent_b = pool.get(0).unwrap();
-                    for (parent_bi, rep) in pool.iter().enumerate() {
-                        if parent_bi == parent_ai
-                            || (parent_bi != parent_ai
-                                && parent_a.dist(parent_b) > parent_a.dist(rep))
-                        {
-                            parent_b = rep;
-                        }
-                    }
-                    parent_a.to_genome().mix(&parent_b.to_genome())
+    /// Plays a round with `copies` of the members of `pool` swapped for
+    /// `genome`, returning the finished round and the indexes of the copies.
+    fn replay(&self, genome: &NetGenome, pool: usize, copies: usize) -> (Server, Vec<usize>) {
+        let mut eval = self.clone();
+        eval.auto_save = None;
+        let mut slots: Vec<_> = (0..eval.sim.replicants.len())
+            .filter(|i| eval.sim.replicants[*i].net.pool() == pool)
+            .collect();
+        slots.truncate(copies);
+        eval.sim.replicants = eval
+            .sim
+            .replicants
+            .iter()
+            .enumerate()
+            .map(|(i, rep)| {
+                if slots.contains(&i) {
+                    Replicant::from_genome(genome)
                 } else {
-                    parent_a.to_genome()
-                };
+                    Replicant::from_genome(&rep.to_genome())
+                }
+            })
+            .collect();
+        let length = self.round_length.sample(self.generation, 0);
+        eval.sim.schedule(length + 1);
+        eval.sim.setup();
+        for _ in 0..=length {
+            eval.sim.tick();
+        }
+        (eval, slots)
+    }
 
-                let pmut = if x == 0 {
-                    0.9
-                } else if x == 1 {
-                    0.95
-                } else {
-                    0.99
-                };
-                if random::<f32>() > pmut {
-                    genome.randomize();
+    fn finish_round(&mut self) {
+        self.score_genes();
+        self.print_pools_stats();
+        let survival =
+            self.survival().iter().sum::<f32>() / self.sim.replicants.len().max(1) as f32;
+        let changes = self.schedule.due(self.generation + 1, Some(survival));
+        let arms_race = self.arms_race();
+        self.write_metrics(&changes, arms_race);
+        let population = Population {
+            sim: &self.sim,
+            genomes: self
+                .sim
+                .replicants
+                .iter()
+                .map(|rep| rep.to_genome())
+                .collect(),
+            fitness: self.scores(),
+            gene_pools: &self.gene_pools,
+            pools: &self.pools,
+            simplify_rate: self.simplify_rate,
+            tie_break: self.parsimony.tie_break,
+            limits: self.limits,
+        };
+        let mut children = self.strategy.breed(&population, self.pop_size);
+        let moved = self.pool_migration.apply(&mut children, self.pools.len());
+        if moved > 0 {
+            eprintln!("{} children changed pool", moved);
+        }
+        self.sim.replicants = children.iter().map(Replicant::from_genome).collect();
+        self.change_world(self.generation + 1, &changes);
+    }
+    /// Makes `changes` to the world, but those that would leave too few
+    /// free cells for the population.
+    fn change_world(&mut self, generation: usize, changes: &[Change]) {
+        for change in changes {
+            let mut world = self.sim.world.clone();
+            change.apply(&mut world);
+            if world.free_cells() < self.pop_size {
+                eprintln!(
+                    "generation {}: rejected {}, {} free cells for {} replicants",
+                    generation,
+                    change,
+                    world.free_cells(),
+                    self.pop_size
+                );
+                continue;
+            }
+            eprintln!("generation {}: {}", generation, change);
+            self.sim.world = world;
+        }
+    }
+    /// Survivors of each pool relative to an even split of the population,
+    /// averaged over the episodes of the round.
+    fn fractions(&self) -> Vec<f32> {
+        let n = self.pools.len();
+        let mut survivors = vec![0.0; n];
+        for (rep, survival) in self.sim.replicants.iter().zip(self.survival()) {
+            if let Some(total) = survivors.get_mut(rep.net.pool()) {
+                *total += survival;
+            }
+        }
+        survivors
+            .iter()
+            .map(|survivors| survivors * n as f32 / self.pop_size as f32)
+            .collect()
+    }
+    fn print_pools_stats(&mut self) {
+        let fractions = self.fractions();
+        let island = match self.island {
+            Some(i) => format!("{} ", i),
+            None => String::new(),
+        };
+        let fractions: Vec<String> = fractions.iter().map(|f| format!("{:.3}", f)).collect();
+        println!("{}{}", island, fractions.join(" "));
+    }
+    /// Cross-evaluation of the scenario's pools against past opponents, every
+    /// `arms_race_interval` generations.
+    fn arms_race(&self) -> Vec<ArmsRace> {
+        let scenario = match &self.sim.world.scenario {
+            Some(scenario) if self.arms_race_interval > 0 => scenario,
+            _ => return vec![],
+        };
+        if self.generation % self.arms_race_interval != 0 {
+            return vec![];
+        }
+        let races = coevolution::arms_race(self, scenario, ARMS_RACE_SAMPLES);
+        for race in &races {
+            let past: Vec<String> = race
+                .past
+                .iter()
+                .map(|(generation, score)| format!("{}:{:.3}", generation, score))
+                .collect();
+            eprintln!(
+                "pool {} against pool {}: {:.3} today, {}",
+                race.pool,
+                race.opponent,
+                race.current,
+                past.join(" ")
+            );
+        }
+        races
+    }
+    /// Appends this generation's pool reports to `{save}.metrics.jsonl`.
+    fn write_metrics(&self, changes: &[Change], arms_race: Vec<ArmsRace>) {
+        let path = match &self.auto_save {
+            Some(path) => format!("{}.metrics.jsonl", path.to_string_lossy()),
+            None => return,
+        };
+        let metrics = Metrics {
+            generation: self.generation,
+            fractions: self.fractions(),
+            pools: report::reports(self, 5),
+            pruned: self.pruned.clone(),
+            objectives: match &self.strategy {
+                Strategy::Nsga2(nsga2) => nsga::objective_stats(&nsga2.objectives, &self.sim),
+                _ => vec![],
+            },
+            arms_race,
+            changes: changes.to_vec(),
+        };
+        let line = serde_json::to_string(&metrics).unwrap();
+        let file = OpenOptions::new().create(true).append(true).open(&path);
+        if let Err(e) = file.and_then(|mut file| writeln!(file, "{}", line)) {
+            eprintln!("cannot write {}: {}", path, e);
+        }
+    }
+    pub fn pool_name(&self, pool: usize) -> String {
+        let configs = if self.pools.is_empty() {
+            PoolConfig::defaults(3)
+        } else {
+            self.pools.clone()
+        };
+        configs
+            .get(pool)
+            .map_or_else(|| format!("pool-{}", pool), |config| config.name.clone())
+    }
+    /// The genome each pool would currently assemble, sorted by pool.
+    pub fn champions(&self) -> Vec<(usize, NetGenome)> {
+        let mut champions: Vec<_> = self
+            .gene_pools
+            .iter()
+            .map(|(pool_i, pool)| {
+                (
+                    *pool_i,
+                    GenePoolAssembly::assemble(*pool_i, pool, &self.limits),
+                )
+            })
+            .collect();
+        champions.sort_by_key(|(pool_i, _)| *pool_i);
+        champions
+    }
+    /// Adds the genes of `genome` to the gene pool of `pool` with the best
+    /// score seen so far, so they are assembled into the next generation.
+    pub fn inject(&mut self, genome: &NetGenome, pool: usize) {
+        let pool = self.gene_pools.entry(pool).or_insert_with(GenePool::new);
+        for (gene, node) in &genome.nodes {
+            pool.seed(gene, node);
+        }
+    }
+    /// Drops the pools from `n` on, with their gene pools and settings. Their
+    /// members move to the pool their index wraps to, and the round in
+    /// progress is restarted.
+    pub fn shrink_pools(&mut self, n: usize) {
+        if n >= self.pools.len() {
+            return;
+        }
+        self.pools.truncate(n);
+        self.gene_pools.retain(|pool, _| *pool < n);
+        self.pruned.retain(|pool, _| *pool < n);
+        self.fitness.retain(|pool, _| *pool < n);
+        net::set_pools(n);
+        self.sim.replicants = self
+            .sim
+            .replicants
+            .iter()
+            .map(|rep| {
+                let mut genome = rep.to_genome();
+                if genome.pool() >= n {
+                    genome.set_pool(genome.pool() % n);
                 }
-                new_reps.push(Replicant::from_genome(&genome));
-            } else {
-                let mut rep = Replicant::default();
-                // rep.net.randomize();
-                rep.net.color[x] = 1.0;
-                new_reps.push(rep);
+                Replicant::from_genome(&genome)
+            })
+            .collect();
+        self.time = 0;
+        self.episode = 0;
+        self.episode_scores.clear();
+        self.episode_survival.clear();
+    }
+    /// Directory the genomes handed to a running save are picked up from.
+    pub fn import_dir(save: &std::path::Path) -> PathBuf {
+        PathBuf::from(format!("{}.import", save.to_string_lossy()))
+    }
+    /// Injects the `*.json` genomes waiting in the import directory of the
+    /// save into the pool of each genome, deleting them once read.
+    fn import_pending(&mut self) {
+        let dir = match &self.auto_save {
+            Some(path) => Self::import_dir(path),
+            None => return,
+        };
+        let entries = match std::fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(_) => return,
+        };
+        let mut paths: Vec<_> = entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension().map_or(false, |ext| ext == "json"))
+            .collect();
+        paths.sort();
+        for path in paths {
+            let json = match std::fs::read_to_string(&path) {
+                Ok(json) => json,
+                Err(_) => continue,
+            };
+            match NetGenome::from_json(&json) {
+                Ok(genome) => {
+                    self.inject(&genome, genome.pool());
+                    eprintln!("imported {}", path.to_string_lossy());
+                }
+                Err(e) => eprintln!("skipping {}: {}", path.to_string_lossy(), e),
             }
-
-            // let i = i % pool.len();
-
-            // if i == 0 {
-            //     parent_a.net.randomize();
-            // }
+            let _ = std::fs::remove_file(&path);
         }
-        self.sim.replicants = new_reps;
     }
 }
 
@@ -1,21 +1,165 @@
-use std::collections::{HashMap, HashSet};
+use std::{
+    collections::{HashMap, HashSet},
+    str::FromStr,
+};
 
-use rand::random;
+use rand::{random, Rng, SeedableRng};
+use rand_pcg::Pcg32;
 use serde::{Deserialize, Serialize};
 
 use crate::{input::NeighbourType, replicant::Replicant, world::World};
 
+/// When survival is sampled during a round.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Evaluation {
+    /// Alive on the last tick.
+    FinalTick,
+    /// Fraction of the ticks spent alive, i.e. the cumulative time in the
+    /// zone counted on every tick.
+    FractionAlive,
+    /// Fraction of `n` random ticks, the same for everyone, spent alive.
+    Checkpoints(usize),
+}
+
+impl Default for Evaluation {
+    fn default() -> Self {
+        Evaluation::FinalTick
+    }
+}
+
+impl FromStr for Evaluation {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            Some(("checkpoints", n)) => n
+                .parse()
+                .map(Evaluation::Checkpoints)
+                .map_err(|e| format!("{:?}: {}", n, e)),
+            None if s == "final" => Ok(Evaluation::FinalTick),
+            None if s == "fraction" => Ok(Evaluation::FractionAlive),
+            _ => Err(format!(
+                "unknown evaluation {:?}, expected final, fraction or checkpoints:<n>",
+                s
+            )),
+        }
+    }
+}
+
+/// How many ticks a round lasts, drawn again every round.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum RoundLength {
+    Fixed(usize),
+    /// Between `min` and `max`, both included.
+    Uniform {
+        min: usize,
+        max: usize,
+    },
+    /// At least `min`, with a constant chance of ending on every later tick
+    /// so that rounds last `mean` ticks on average.
+    Geometric {
+        min: usize,
+        mean: usize,
+    },
+}
+
+impl Default for RoundLength {
+    fn default() -> Self {
+        RoundLength::Fixed(300)
+    }
+}
+
+/// Parses `fixed:<ticks>`, `uniform:<min>,<max>` or `geometric:<min>,<mean>`.
+impl FromStr for RoundLength {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, arg) = s.split_once(':').unwrap_or((s, ""));
+        let numbers = arg
+            .split(',')
+            .map(|n| {
+                n.trim()
+                    .parse::<usize>()
+                    .map_err(|e| format!("{:?}: {}", n, e))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        match (name, &numbers[..]) {
+            ("fixed", [n]) => Ok(RoundLength::Fixed(*n)),
+            ("uniform", [min, max]) if min <= max => Ok(RoundLength::Uniform {
+                min: *min,
+                max: *max,
+            }),
+            ("geometric", [min, mean]) if min <= mean => Ok(RoundLength::Geometric {
+                min: *min,
+                mean: *mean,
+            }),
+            _ => Err(format!(
+                "unknown round length {:?}, expected fixed:<ticks>, uniform:<min>,<max> or geometric:<min>,<mean>",
+                s
+            )),
+        }
+    }
+}
+
+impl RoundLength {
+    /// Length of an episode of a generation, the same for every run with
+    /// the same arguments.
+    pub fn sample(&self, generation: usize, episode: usize) -> usize {
+        let mut rng = Pcg32::seed_from_u64(((generation as u64) << 16) ^ episode as u64);
+        match *self {
+            RoundLength::Fixed(n) => n,
+            RoundLength::Uniform { min, max } => rng.gen_range(min..=max),
+            RoundLength::Geometric { min, mean } => {
+                let p = 1.0 / (mean - min + 1) as f64;
+                let u = rng.gen::<f64>().max(f64::MIN_POSITIVE);
+                // Capped so that a freak draw cannot stall the run.
+                min + ((u.ln() / (1.0 - p).ln()) as usize).min(10 * (mean - min))
+            }
+        }
+    }
+}
+
 #[derive(Default, Clone, Debug, Serialize, Deserialize)]
 pub struct Simulation {
     pub world: World,
     pub replicants: Vec<Replicant>,
     // #[serde(skip_serializing)]
     pub mapper: CellMapper,
+    pub evaluation: Evaluation,
+    /// Ticks at which `Evaluation::Checkpoints` samples survival.
+    pub checkpoints: Vec<usize>,
 }
 
 impl Simulation {
+    /// Draws the distinct checkpoints of a round lasting `length` ticks.
+    pub fn schedule(&mut self, length: usize) {
+        self.checkpoints = match self.evaluation {
+            Evaluation::Checkpoints(n) => {
+                rand::seq::index::sample(&mut rand::thread_rng(), length, n.min(length)).into_vec()
+            }
+            _ => vec![],
+        };
+    }
+
+    /// How much of the round `rep` survived, according to `self.evaluation`.
+    pub fn survival(&self, rep: &Replicant) -> f32 {
+        match self.evaluation {
+            Evaluation::FinalTick => rep.is_alive(&self.world, &self.mapper) as u8 as f32,
+            Evaluation::FractionAlive => rep.alive_ticks as f32 / rep.time.max(1) as f32,
+            Evaluation::Checkpoints(_) => {
+                rep.checkpoints_alive as f32 / self.checkpoints.len().max(1) as f32
+            }
+        }
+    }
+
+    /// Whether `rep` counts as a survivor of the round.
+    pub fn survived(&self, rep: &Replicant) -> bool {
+        self.survival(rep) >= 0.5
+    }
+
     pub fn setup(&mut self) {
         self.mapper.reset();
+        for (x, y) in &self.world.obstacles {
+            self.mapper.block(*x, *y);
+        }
         self.replicants.iter_mut().for_each(|rep| {
             while !self.mapper.add_abs(
                 &mut rep.pos,
@@ -25,6 +169,7 @@ impl Simulation {
                 ),
                 rep.net.pool(),
             ) {}
+            rep.visited.insert(rep.pos);
         });
     }
 
@@ -35,8 +180,26 @@ impl Simulation {
             .par_iter_mut()
             .enumerate()
             .map(|(rep_i, rep)| {
+                if rep.caught.is_some() {
+                    rep.time += 1;
+                    return (rep_i, vec![]);
+                }
                 let is_alive = rep.is_alive(&self.world, &self.mapper);
+                rep.alive_ticks += is_alive as usize;
+                if is_alive && self.checkpoints.contains(&rep.time) {
+                    rep.checkpoints_alive += 1;
+                }
                 let pool = rep.net.pool();
+                let opponent = self.world.scenario.and_then(|s| s.opponent(pool));
+                if let Some(opponent) = opponent {
+                    let (x, y) = rep.pos;
+                    if [(1, 0), (-1, 0), (0, 1), (0, -1)]
+                        .iter()
+                        .any(|(dx, dy)| self.mapper.is(x + dx, y + dy, opponent))
+                    {
+                        rep.contacts += 1;
+                    }
+                }
                 rep.net
                     .sensors
                     .iter_mut()
@@ -84,37 +247,83 @@ impl Simulation {
         actions.iter().for_each(|(rep_i, actions)| {
             let rep = self.replicants.get_mut(*rep_i).unwrap();
             actions.iter().for_each(|action| {
+                rep.actions[*action as usize] += 1;
                 match action {
                     crate::actions::Action::IncX => {
-                        if self.mapper.clip.is_some() || rep.pos.0 + 1 < self.world.width {
+                        if self.mapper.clip.is_some()
+                            || self.world.contains((rep.pos.0 + 1, rep.pos.1))
+                        {
                             self.mapper.move_rel(&mut rep.pos, (1, 0), rep.net.pool());
                             rep.moves += 1;
                         }
                     }
                     crate::actions::Action::IncY => {
-                        if self.mapper.clip.is_some() || rep.pos.1 + 1 < self.world.height {
+                        if self.mapper.clip.is_some()
+                            || self.world.contains((rep.pos.0, rep.pos.1 + 1))
+                        {
                             self.mapper.move_rel(&mut rep.pos, (0, 1), rep.net.pool());
                             rep.moves += 1;
                         }
                     }
                     crate::actions::Action::DecX => {
-                        if self.mapper.clip.is_some() || rep.pos.0 - 1 > 0 {
+                        if self.mapper.clip.is_some()
+                            || self.world.contains((rep.pos.0 - 1, rep.pos.1))
+                        {
                             self.mapper.move_rel(&mut rep.pos, (-1, 0), rep.net.pool());
                             rep.moves += 1;
                         }
                     }
                     crate::actions::Action::DecY => {
-                        if self.mapper.clip.is_some() || rep.pos.1 - 1 > 0 {
+                        if self.mapper.clip.is_some()
+                            || self.world.contains((rep.pos.0, rep.pos.1 - 1))
+                        {
                             self.mapper.move_rel(&mut rep.pos, (0, -1), rep.net.pool());
                             rep.moves += 1;
                         }
                     }
                 };
             });
+            rep.visited.insert(rep.pos);
         });
+        if let Some((predator, prey)) = self.world.scenario.and_then(|s| s.hunt()) {
+            self.capture(predator, prey);
+        }
+    }
+
+    /// Takes every prey next to a predator out of the world, crediting the
+    /// capture to one of the predators it met.
+    fn capture(&mut self, predator: usize, prey: usize) {
+        const NEIGHBOURS: [(i32, i32); 4] = [(1, 0), (-1, 0), (0, 1), (0, -1)];
+        for i in 0..self.replicants.len() {
+            let rep = &self.replicants[i];
+            let (x, y) = rep.pos;
+            if rep.net.pool() != prey
+                || rep.caught.is_some()
+                || !NEIGHBOURS
+                    .iter()
+                    .any(|(dx, dy)| self.mapper.is(x + dx, y + dy, predator))
+            {
+                continue;
+            }
+            let hunter = self.replicants.iter().position(|other| {
+                other.net.pool() == predator
+                    && NEIGHBOURS
+                        .iter()
+                        .any(|(dx, dy)| self.mapper.normalize(x + dx, y + dy) == other.pos)
+            });
+            if let Some(hunter) = hunter {
+                self.replicants[hunter].captures += 1;
+            }
+            let rep = &mut self.replicants[i];
+            rep.caught = Some(rep.time);
+            self.mapper.remove(x, y);
+        }
     }
 }
 
+/// What `CellMapper` holds instead of a pool on the cells of obstacles.
+pub const OBSTACLE: usize = usize::MAX;
+
 #[derive(Clone, Default, Debug, Serialize, Deserialize)]
 pub struct CellMapper {
     pub clip: Option<(i32, i32)>,
@@ -124,6 +333,11 @@ impl CellMapper {
     pub fn reset(&mut self) {
         self.filled_cells.clear();
     }
+    /// Fills a cell with an obstacle.
+    pub fn block(&mut self, x: i32, y: i32) {
+        let cell = self.normalize(x, y);
+        self.filled_cells.insert(cell, OBSTACLE);
+    }
     pub fn normalize(&self, x: i32, y: i32) -> (i32, i32) {
         match self.clip {
             None => (x, y),
@@ -145,6 +359,11 @@ impl CellMapper {
             ),
         }
     }
+    /// Empties a cell.
+    pub fn remove(&mut self, x: i32, y: i32) {
+        let cell = self.normalize(x, y);
+        self.filled_cells.remove(&cell);
+    }
     pub fn has(&self, x: i32, y: i32) -> bool {
         let (x, y) = self.normalize(x, y);
         self.filled_cells.contains_key(&(x, y))
@@ -210,3 +429,47 @@ impl CellMapper {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        coevolution::Scenario,
+        fitness::{Fitness, FitnessFn},
+    };
+
+    fn place(sim: &mut Simulation, pool: usize, pos: (i32, i32)) {
+        let mut rep = Replicant::default();
+        rep.net.pool = pool;
+        rep.time = 10;
+        assert!(sim.mapper.add_abs(&mut rep.pos, pos, pool));
+        sim.replicants.push(rep);
+    }
+
+    #[test]
+    fn predators_catch_neighbouring_prey() {
+        let mut sim = Simulation::default();
+        sim.world.width = 10;
+        sim.world.height = 10;
+        sim.world.scenario = Some(Scenario::PredatorPrey {
+            predator: 0,
+            prey: 1,
+        });
+        place(&mut sim, 0, (2, 2));
+        place(&mut sim, 1, (3, 2));
+        place(&mut sim, 1, (7, 7));
+        sim.capture(0, 1);
+
+        assert_eq!(sim.replicants[0].captures, 1);
+        assert_eq!(sim.replicants[1].caught, Some(10));
+        assert!(!sim.mapper.has(3, 2));
+        assert_eq!(sim.replicants[2].caught, None);
+        assert!(!sim.replicants[1].is_alive(&sim.world, &sim.mapper));
+
+        // The caught prey stops playing while the round goes on.
+        sim.replicants.iter_mut().for_each(|rep| rep.time = 20);
+        assert_eq!(FitnessFn::Evasion.score(&sim.replicants[1], &sim), 0.5);
+        assert_eq!(FitnessFn::Evasion.score(&sim.replicants[2], &sim), 1.0);
+        assert_eq!(FitnessFn::Captures.score(&sim.replicants[0], &sim), 0.5);
+    }
+}
@@ -0,0 +1,336 @@
+use std::{
+    cmp::Ordering,
+    collections::{HashMap, HashSet},
+};
+
+use rand::random;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    genome::Genome,
+    net::{NetGenome, NeuralSource, NeuralTarget},
+};
+
+/// Historical markings shared by the whole run: every structural gene gets a
+/// number when it is created, so genomes can be aligned gene by gene.
+#[derive(Default, Clone, Serialize, Deserialize)]
+pub struct Innovations {
+    next: u64,
+    links: HashMap<(NeuralSource, NeuralTarget), u64>,
+    hidden: HashMap<NeuralTarget, u64>,
+}
+
+impl Innovations {
+    fn link(&mut self, source: &NeuralSource, target: &NeuralTarget) -> u64 {
+        let key = (source.clone(), target.clone());
+        if let Some(innovation) = self.links.get(&key) {
+            return *innovation;
+        }
+        self.next += 1;
+        self.links.insert(key, self.next);
+        self.next
+    }
+    fn hidden(&mut self, target: &NeuralTarget) -> u64 {
+        if let Some(innovation) = self.hidden.get(target) {
+            return *innovation;
+        }
+        self.next += 1;
+        self.hidden.insert(target.clone(), self.next);
+        self.next
+    }
+    /// Numbers the genes of `genome` that have none yet. Called on every
+    /// child as soon as mutation has added its new genes.
+    pub fn register(&mut self, genome: &NetGenome) {
+        for (target, node) in &genome.nodes {
+            if let NeuralTarget::Hidden(_) = target {
+                self.hidden(target);
+            }
+            for source in node.inputs.keys() {
+                self.link(source, target);
+            }
+        }
+    }
+    /// Forgets the genes none of `genomes` has anymore.
+    pub fn retain<'a>(&mut self, genomes: impl IntoIterator<Item = &'a NetGenome>) {
+        let mut links = HashSet::new();
+        let mut hidden = HashSet::new();
+        for genome in genomes {
+            for (target, node) in &genome.nodes {
+                hidden.insert(target.clone());
+                for source in node.inputs.keys() {
+                    links.insert((source.clone(), target.clone()));
+                }
+            }
+        }
+        self.links.retain(|key, _| links.contains(key));
+        self.hidden.retain(|target, _| hidden.contains(target));
+    }
+    /// Returns the genes of `genome` keyed by innovation number, with the link
+    /// weight as value (hidden neurons carry no weight). Genes that were
+    /// never registered count as the newest.
+    fn genes(&self, genome: &NetGenome) -> HashMap<u64, Option<f32>> {
+        let mut genes = HashMap::new();
+        let mut unknown = u64::MAX;
+        let mut number = |innovation: Option<&u64>| {
+            innovation.cloned().unwrap_or_else(|| {
+                unknown -= 1;
+                unknown
+            })
+        };
+        for (target, node) in &genome.nodes {
+            if let NeuralTarget::Hidden(_) = target {
+                genes.insert(number(self.hidden.get(target)), None);
+            }
+            for (source, link) in &node.inputs {
+                let key = (source.clone(), target.clone());
+                genes.insert(number(self.links.get(&key)), Some(link.weight));
+            }
+        }
+        genes
+    }
+}
+
+/// Coefficients of the NEAT compatibility distance
+/// `excess * E / N + disjoint * D / N + weight * W`.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Compatibility {
+    pub excess: f32,
+    pub disjoint: f32,
+    pub weight: f32,
+    pub threshold: f32,
+}
+
+impl Default for Compatibility {
+    fn default() -> Self {
+        Self {
+            excess: 1.0,
+            disjoint: 1.0,
+            weight: 0.4,
+            threshold: 3.0,
+        }
+    }
+}
+
+impl Compatibility {
+    pub fn distance(&self, innovations: &Innovations, a: &NetGenome, b: &NetGenome) -> f32 {
+        let a = innovations.genes(a);
+        let b = innovations.genes(b);
+        let a_max = a.keys().max().cloned().unwrap_or(0);
+        let b_max = b.keys().max().cloned().unwrap_or(0);
+        let mut excess = 0;
+        let mut disjoint = 0;
+        let mut matching = 0;
+        let mut weight_diff = 0.0;
+        for (innovation, weight) in &a {
+            match b.get(innovation) {
+                Some(other) => {
+                    if let (Some(x), Some(y)) = (weight, other) {
+                        matching += 1;
+                        weight_diff += (x - y).abs();
+                    }
+                }
+                None if *innovation > b_max => excess += 1,
+                None => disjoint += 1,
+            }
+        }
+        for innovation in b.keys() {
+            if !a.contains_key(innovation) {
+                if *innovation > a_max {
+                    excess += 1;
+                } else {
+                    disjoint += 1;
+                }
+            }
+        }
+        let n = a.len().max(b.len());
+        // Small genomes are not normalised, as in the original NEAT paper.
+        let n = if n < 20 { 1.0 } else { n as f32 };
+        let w = if matching > 0 {
+            weight_diff / matching as f32
+        } else {
+            0.0
+        };
+        self.excess * excess as f32 / n + self.disjoint * disjoint as f32 / n + self.weight * w
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Species {
+    pub id: usize,
+    pub representative: NetGenome,
+    pub members: Vec<usize>,
+    /// Best raw fitness ever reached by a member.
+    pub best: f32,
+    /// Generations since `best` last improved.
+    pub stagnation: usize,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Neat {
+    pub innovations: Innovations,
+    pub compatibility: Compatibility,
+    pub species: Vec<Species>,
+    /// Generations without improvement after which a species is culled.
+    pub stagnation_limit: usize,
+    /// Fraction of each species allowed to reproduce.
+    pub survival_rate: f32,
+    pub crossover_rate: f32,
+    pub mutation_rate: f32,
+    next_species: usize,
+}
+
+impl Default for Neat {
+    fn default() -> Self {
+        Self {
+            innovations: Default::default(),
+            compatibility: Default::default(),
+            species: vec![],
+            stagnation_limit: 15,
+            survival_rate: 0.2,
+            crossover_rate: 0.75,
+            mutation_rate: 0.8,
+            next_species: 0,
+        }
+    }
+}
+
+impl Neat {
+    /// Assigns every genome to the first compatible species, creating new
+    /// species as needed and dropping the ones left empty.
+    pub fn speciate(&mut self, genomes: &[NetGenome]) {
+        self.species.iter_mut().for_each(|s| s.members.clear());
+        for (i, genome) in genomes.iter().enumerate() {
+            let found = self.species.iter().position(|s| {
+                self.compatibility
+                    .distance(&self.innovations, &s.representative, genome)
+                    < self.compatibility.threshold
+            });
+            match found {
+                Some(s) => self.species[s].members.push(i),
+                None => {
+                    self.next_species += 1;
+                    self.species.push(Species {
+                        id: self.next_species,
+                        representative: genome.clone(),
+                        members: vec![i],
+                        best: f32::MIN,
+                        stagnation: 0,
+                    });
+                }
+            }
+        }
+        self.species.retain(|s| !s.members.is_empty());
+    }
+
+    pub fn reproduce(
+        &mut self,
+        genomes: &[NetGenome],
+        fitness: &[f32],
+        pop_size: usize,
+    ) -> Vec<NetGenome> {
+        // Genomes that did not come from here, the first generation, seeds
+        // and migrants, bring genes without a number.
+        for genome in genomes {
+            self.innovations.register(genome);
+        }
+        self.speciate(genomes);
+
+        for species in &mut self.species {
+            let best = species
+                .members
+                .iter()
+                .map(|i| fitness[*i])
+                .fold(f32::MIN, f32::max);
+            if best > species.best {
+                species.best = best;
+                species.stagnation = 0;
+            } else {
+                species.stagnation += 1;
+            }
+        }
+
+        // Cull stagnant species, but never the one holding the best genome.
+        let champion = self.species.iter().map(|s| s.best).fold(f32::MIN, f32::max);
+        let limit = self.stagnation_limit;
+        self.species
+            .retain(|s| s.stagnation <= limit || s.best >= champion);
+
+        // Fitness sharing: a species' share of the offspring is the sum of its
+        // members' fitness divided by its size.
+        let shares: Vec<f32> = self
+            .species
+            .iter()
+            .map(|s| {
+                s.members.iter().map(|i| fitness[*i].max(0.0)).sum::<f32>() / s.members.len() as f32
+            })
+            .collect();
+        let total: f32 = shares.iter().sum();
+
+        let mut children = vec![];
+        for (species, share) in self.species.iter_mut().zip(&shares) {
+            let quota = if total > 0.0 {
+                (share / total * pop_size as f32).round() as usize
+            } else {
+                pop_size / shares.len()
+            };
+
+            let mut ranked = species.members.clone();
+            ranked.sort_by(|a, b| {
+                fitness[*b]
+                    .partial_cmp(&fitness[*a])
+                    .unwrap_or(Ordering::Equal)
+            });
+            let parents = ((ranked.len() as f32 * self.survival_rate).ceil() as usize)
+                .max(1)
+                .min(ranked.len());
+            let parents = &ranked[..parents];
+
+            for n in 0..quota {
+                if n == 0 && ranked.len() >= 5 {
+                    // Species champions are copied unchanged.
+                    children.push(genomes[ranked[0]].clone());
+                    continue;
+                }
+                let a = parents[random::<usize>() % parents.len()];
+                let mut child = if random::<f32>() < self.crossover_rate {
+                    let b = parents[random::<usize>() % parents.len()];
+                    let (fit, other) = if fitness[a] >= fitness[b] {
+                        (a, b)
+                    } else {
+                        (b, a)
+                    };
+                    genomes[fit].mix(&genomes[other])
+                } else {
+                    genomes[a].clone()
+                };
+                if random::<f32>() < self.mutation_rate {
+                    child.randomize();
+                    self.innovations.register(&child);
+                }
+                children.push(child);
+            }
+
+            species.representative =
+                genomes[species.members[random::<usize>() % species.members.len()]].clone();
+        }
+
+        // Rounding may leave the population slightly off its target size.
+        children.truncate(pop_size);
+        while children.len() < pop_size {
+            let parents = if children.is_empty() {
+                genomes
+            } else {
+                &children
+            };
+            let mut child = parents[random::<usize>() % parents.len()].clone();
+            child.randomize();
+            self.innovations.register(&child);
+            children.push(child);
+        }
+        // Representatives are compared with the next generation.
+        let representatives = self.species.iter().map(|s| &s.representative);
+        self.innovations
+            .retain(children.iter().chain(representatives));
+        children
+    }
+}
@@ -0,0 +1,685 @@
+use std::{cmp::Ordering, collections::HashMap, str::FromStr};
+
+use rand::random;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    behaviour::{Descriptor, EliteGrid, NoveltyArchive},
+    fitness::Objective,
+    genome::Genome,
+    net::{NetGenome, NeuralNode, NeuralTarget},
+    nsga,
+    parsimony::{self, Limits},
+    pool::{GenePool, PoolConfig},
+    simulation::Simulation,
+    species::Neat,
+};
+
+/// The evaluated generation a strategy breeds from.
+pub struct Population<'a> {
+    /// The round that just ended.
+    pub sim: &'a Simulation,
+    /// Genome of each replicant.
+    pub genomes: Vec<NetGenome>,
+    /// Fitness of each replicant, by its pool's fitness function.
+    pub fitness: Vec<f32>,
+    pub gene_pools: &'a HashMap<usize, GenePool<NeuralTarget, NeuralNode>>,
+    /// Configuration of each colour pool.
+    pub pools: &'a [PoolConfig],
+    /// Probability of replacing a child with its simplified genome.
+    pub simplify_rate: f32,
+    /// Prefer smaller genomes between equally fit ones.
+    pub tie_break: bool,
+    pub limits: Limits,
+}
+
+impl<'a> Population<'a> {
+    /// Indexes of the members of each pool.
+    fn by_pool(&self) -> Vec<Vec<usize>> {
+        let mut pools = vec![vec![]; self.pools.len()];
+        for (i, genome) in self.genomes.iter().enumerate() {
+            pools[genome.pool().min(self.pools.len() - 1)].push(i);
+        }
+        pools
+    }
+    /// Orders `a` and `b` by fitness, the fitter one greater.
+    fn compare(&self, a: usize, b: usize) -> Ordering {
+        let order = self.fitness[a]
+            .partial_cmp(&self.fitness[b])
+            .unwrap_or(Ordering::Equal);
+        if self.tie_break {
+            let links = |i: usize| parsimony::size(&self.genomes[i].nodes).1;
+            order.then_with(|| links(b).cmp(&links(a)))
+        } else {
+            order
+        }
+    }
+    /// Indexes sorted from the fittest.
+    fn ranked(&self, members: &[usize]) -> Vec<usize> {
+        let mut ranked = members.to_vec();
+        ranked.sort_by(|a, b| self.compare(*b, *a));
+        ranked
+    }
+    /// Behaviour of each replicant.
+    fn behaviours(&self, descriptor: &Descriptor) -> Vec<Vec<f32>> {
+        self.sim
+            .replicants
+            .iter()
+            .map(|rep| descriptor.describe(rep, &self.sim.world))
+            .collect()
+    }
+    fn finish(&self, genome: NetGenome) -> NetGenome {
+        if random::<f32>() < self.simplify_rate {
+            genome.simplified(0.0)
+        } else {
+            genome
+        }
+    }
+}
+
+/// Splits `count` children evenly among `pools`.
+fn quotas(count: usize, pools: usize) -> Vec<usize> {
+    (0..pools)
+        .map(|i| count / pools + if i < count % pools { 1 } else { 0 })
+        .collect()
+}
+
+/// A random genome of pool `pool`, for pools left without members.
+fn founder(pool: usize) -> NetGenome {
+    let mut genome = NetGenome::default();
+    genome.set_pool(pool);
+    genome.randomize();
+    genome
+}
+
+pub trait EvolutionStrategy {
+    /// Breeds `count` genomes for the next generation.
+    fn breed(&mut self, population: &Population, count: usize) -> Vec<NetGenome>;
+}
+
+/// How `Generational` picks parents.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum ParentSelection {
+    /// Fittest of `size` random members.
+    Tournament { size: usize },
+    /// Random, proportionally to fitness.
+    Roulette,
+}
+
+/// How `Generational` picks the second parent of a crossover.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Mating {
+    /// Same as the first one.
+    Selected,
+    /// Closest member of the pool at the end of the round.
+    Nearest,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Generational {
+    pub selection: ParentSelection,
+    pub mating: Mating,
+    pub crossover_rate: f32,
+    pub mutation_rate: f32,
+}
+
+impl Default for Generational {
+    fn default() -> Self {
+        Self {
+            selection: ParentSelection::Tournament { size: 3 },
+            mating: Mating::Selected,
+            crossover_rate: 0.1,
+            mutation_rate: 0.1,
+        }
+    }
+}
+
+impl Generational {
+    fn select(&self, population: &Population, members: &[usize]) -> usize {
+        match self.selection {
+            ParentSelection::Tournament { size } => (0..size.max(1))
+                .map(|_| members[random::<usize>() % members.len()])
+                .max_by(|a, b| population.compare(*a, *b))
+                .unwrap(),
+            ParentSelection::Roulette => {
+                let total: f32 = members
+                    .iter()
+                    .map(|i| population.fitness[*i].max(0.0))
+                    .sum();
+                let mut x = random::<f32>() * total;
+                for i in members {
+                    x -= population.fitness[*i].max(0.0);
+                    if x <= 0.0 {
+                        return *i;
+                    }
+                }
+                members[random::<usize>() % members.len()]
+            }
+        }
+    }
+}
+
+impl EvolutionStrategy for Generational {
+    fn breed(&mut self, population: &Population, count: usize) -> Vec<NetGenome> {
+        let mut children = vec![];
+        let pools = population.by_pool();
+        for (pool, quota) in quotas(count, population.pools.len())
+            .into_iter()
+            .enumerate()
+        {
+            let members = &pools[pool];
+            for _ in 0..quota {
+                if members.is_empty() {
+                    children.push(founder(pool));
+                    continue;
+                }
+                let a = self.select(population, members);
+                let mut child = if random::<f32>() < self.crossover_rate {
+                    let b = match self.mating {
+                        Mating::Selected => self.select(population, members),
+                        Mating::Nearest => *members
+                            .iter()
+                            .filter(|b| **b != a)
+                            .min_by_key(|b| {
+                                let replicants = &population.sim.replicants;
+                                replicants[a].dist(&replicants[**b])
+                            })
+                            .unwrap_or(&a),
+                    };
+                    population.genomes[a].mix(&population.genomes[b])
+                } else {
+                    population.genomes[a].clone()
+                };
+                if random::<f32>() < self.mutation_rate {
+                    child.randomize();
+                }
+                children.push(population.finish(child));
+            }
+        }
+        children
+    }
+}
+
+/// (μ+λ): the `mu` fittest members of each pool are kept unchanged and the
+/// rest of the pool is filled with their mutated copies.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MuPlusLambda {
+    pub mu: usize,
+}
+
+impl EvolutionStrategy for MuPlusLambda {
+    fn breed(&mut self, population: &Population, count: usize) -> Vec<NetGenome> {
+        let mut children = vec![];
+        let pools = population.by_pool();
+        for (pool, quota) in quotas(count, population.pools.len())
+            .into_iter()
+            .enumerate()
+        {
+            let ranked = population.ranked(&pools[pool]);
+            let parents = &ranked[..self.mu.max(1).min(ranked.len()).min(quota)];
+            for n in 0..quota {
+                if parents.is_empty() {
+                    children.push(founder(pool));
+                } else if n < parents.len() {
+                    children.push(population.genomes[parents[n]].clone());
+                } else {
+                    let mut child = population.genomes[parents[n % parents.len()]].clone();
+                    child.randomize();
+                    children.push(population.finish(child));
+                }
+            }
+        }
+        children
+    }
+}
+
+/// Genomes assembled from the best alleles of each colour's gene pool.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct GenePoolAssembly;
+
+impl GenePoolAssembly {
+    /// Builds a genome for `pool_i` out of the best alleles of its gene pool.
+    pub fn assemble(
+        pool_i: usize,
+        pool: &GenePool<NeuralTarget, NeuralNode>,
+        limits: &Limits,
+    ) -> NetGenome {
+        let mut genome = NetGenome::default();
+        let actions: Vec<NeuralTarget> = pool
+            .get_genes()
+            .into_iter()
+            .filter(|gene| match gene {
+                NeuralTarget::Action(_) => true,
+                _ => false,
+            })
+            .collect();
+        // Only hidden neurons count against the build limit, every action
+        // is kept.
+        let hidden = limits.hidden.unwrap_or(usize::MAX).min(
+            limits
+                .nodes
+                .map_or(usize::MAX, |nodes| nodes.saturating_sub(actions.len())),
+        );
+        let alleles = pool.build(actions, hidden);
+        genome.nodes = alleles;
+        genome.repair();
+        genome.limit(limits);
+        genome.set_pool(pool_i);
+        genome
+    }
+}
+
+impl EvolutionStrategy for GenePoolAssembly {
+    fn breed(&mut self, population: &Population, count: usize) -> Vec<NetGenome> {
+        let mut children = vec![];
+        for (pool_i, pool) in population.gene_pools {
+            let mut inserted = 0;
+            while inserted < count / population.gene_pools.len() {
+                let mut genome = Self::assemble(*pool_i, pool, &population.limits);
+                let mutation_rate = population
+                    .pools
+                    .get(*pool_i)
+                    .map_or(0.001, |config| config.mutation_rate);
+                for _ in 0..200 {
+                    if random::<f32>() < mutation_rate {
+                        genome.randomize();
+                    }
+                    genome = population.finish(genome);
+                    children.push(genome.clone());
+                    inserted += 1;
+                }
+            }
+        }
+        children.truncate(count);
+        while children.len() < count {
+            children.push(founder(children.len() % population.pools.len()));
+        }
+        children
+    }
+}
+
+impl EvolutionStrategy for Neat {
+    fn breed(&mut self, population: &Population, count: usize) -> Vec<NetGenome> {
+        let children = self.reproduce(&population.genomes, &population.fitness, count);
+        println!("species: {}", self.species.len());
+        children
+            .into_iter()
+            .map(|genome| population.finish(genome))
+            .collect()
+    }
+}
+
+/// Novelty search: parents are selected by how far their behaviour is from
+/// the rest of the population and the archive, plus `fitness_weight` times
+/// their fitness.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Novelty {
+    pub descriptor: Descriptor,
+    pub archive: NoveltyArchive,
+    pub fitness_weight: f32,
+    pub breeder: Generational,
+}
+
+impl Novelty {
+    pub fn new(descriptor: Descriptor) -> Self {
+        Self {
+            descriptor,
+            archive: Default::default(),
+            fitness_weight: 0.0,
+            breeder: Default::default(),
+        }
+    }
+}
+
+impl EvolutionStrategy for Novelty {
+    fn breed(&mut self, population: &Population, count: usize) -> Vec<NetGenome> {
+        let behaviours = population.behaviours(&self.descriptor);
+        let novelty = self.archive.novelty(&behaviours);
+        self.archive.archive(&behaviours);
+        eprintln!("novelty archive: {}", self.archive.behaviours.len());
+        let scored = Population {
+            genomes: population.genomes.clone(),
+            fitness: novelty
+                .iter()
+                .zip(&population.fitness)
+                .map(|(novelty, fitness)| novelty + self.fitness_weight * fitness)
+                .collect(),
+            ..*population
+        };
+        self.breeder.breed(&scored, count)
+    }
+}
+
+/// MAP-Elites: every genome competes only with the ones behaving alike, and
+/// children are mutated copies of random elites.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct MapElites {
+    pub descriptor: Descriptor,
+    pub grid: EliteGrid,
+    pub crossover_rate: f32,
+}
+
+impl MapElites {
+    pub fn new(descriptor: Descriptor, bins: usize) -> Self {
+        Self {
+            descriptor,
+            grid: EliteGrid::new(bins),
+            crossover_rate: 0.1,
+        }
+    }
+}
+
+impl EvolutionStrategy for MapElites {
+    fn breed(&mut self, population: &Population, count: usize) -> Vec<NetGenome> {
+        let behaviours = population.behaviours(&self.descriptor);
+        for ((genome, fitness), behaviour) in population
+            .genomes
+            .iter()
+            .zip(&population.fitness)
+            .zip(behaviours)
+        {
+            self.grid.insert(genome, *fitness, behaviour);
+        }
+        eprintln!(
+            "elites: {}, qd score: {:.3}",
+            self.grid.cells.len(),
+            self.grid.qd_score()
+        );
+
+        let elites: Vec<&NetGenome> = self.grid.cells.values().map(|e| &e.genome).collect();
+        (0..count)
+            .map(|i| {
+                if elites.is_empty() {
+                    return founder(i % population.pools.len());
+                }
+                let parent = elites[random::<usize>() % elites.len()];
+                let mut child = if random::<f32>() < self.crossover_rate {
+                    parent.mix(elites[random::<usize>() % elites.len()])
+                } else {
+                    parent.clone()
+                };
+                child.randomize();
+                population.finish(child)
+            })
+            .collect()
+    }
+}
+
+/// NSGA-II: parents are ranked by Pareto front, then by crowding distance,
+/// over several objectives instead of a single fitness. The best half of each
+/// pool is kept, the rest is bred by binary tournaments.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Nsga2 {
+    pub objectives: Vec<Objective>,
+    pub crossover_rate: f32,
+    pub mutation_rate: f32,
+}
+
+impl Nsga2 {
+    pub fn new(objectives: Vec<Objective>) -> Self {
+        Self {
+            objectives,
+            crossover_rate: 0.1,
+            mutation_rate: 0.5,
+        }
+    }
+}
+
+impl EvolutionStrategy for Nsga2 {
+    fn breed(&mut self, population: &Population, count: usize) -> Vec<NetGenome> {
+        let sim = population.sim;
+        for stats in nsga::objective_stats(&self.objectives, sim) {
+            eprintln!(
+                "{:?}: mean {:.3}, best {:.3}, front mean {:.3}",
+                stats.objective, stats.mean, stats.best, stats.front_mean
+            );
+        }
+        let mut children = vec![];
+        let pools = population.by_pool();
+        for (pool, quota) in quotas(count, population.pools.len())
+            .into_iter()
+            .enumerate()
+        {
+            let members = &pools[pool];
+            if members.is_empty() {
+                children.extend((0..quota).map(|_| founder(pool)));
+                continue;
+            }
+            let values: Vec<Vec<f32>> = members
+                .iter()
+                .map(|i| {
+                    let rep = &sim.replicants[*i];
+                    self.objectives.iter().map(|o| o.value(rep, sim)).collect()
+                })
+                .collect();
+            // (front, -crowding distance) of each member, lower is better.
+            let mut rank = vec![(0, 0.0); members.len()];
+            for (f, front) in nsga::non_dominated_sort(&values).iter().enumerate() {
+                for (m, d) in front.iter().zip(nsga::crowding_distance(&values, front)) {
+                    rank[*m] = (f, -d);
+                }
+            }
+            let better =
+                |a: usize, b: usize| rank[a].partial_cmp(&rank[b]).unwrap_or(Ordering::Equal);
+            let mut ranked: Vec<usize> = (0..members.len()).collect();
+            ranked.sort_by(|a, b| better(*a, *b));
+
+            let kept = (quota / 2).min(members.len());
+            children.extend(
+                ranked[..kept]
+                    .iter()
+                    .map(|m| population.genomes[members[*m]].clone()),
+            );
+            let tournament = || {
+                let a = random::<usize>() % members.len();
+                let b = random::<usize>() % members.len();
+                if better(a, b) == Ordering::Greater {
+                    b
+                } else {
+                    a
+                }
+            };
+            for _ in kept..quota {
+                let a = &population.genomes[members[tournament()]];
+                let mut child = if random::<f32>() < self.crossover_rate {
+                    a.mix(&population.genomes[members[tournament()]])
+                } else {
+                    a.clone()
+                };
+                if random::<f32>() < self.mutation_rate {
+                    child.randomize();
+                }
+                children.push(population.finish(child));
+            }
+        }
+        children
+    }
+}
+
+/// How a new generation is produced from the previous one.
+#[derive(Clone, Serialize, Deserialize)]
+pub enum Strategy {
+    /// Assemble genomes from the per-colour gene pools.
+    Pools(GenePoolAssembly),
+    /// NEAT speciation with fitness sharing.
+    Neat(Neat),
+    /// Parents selected from each pool, with crossover and mutation.
+    Generational(Generational),
+    MuPlusLambda(MuPlusLambda),
+    Novelty(Novelty),
+    MapElites(MapElites),
+    Nsga2(Nsga2),
+    /// The `count` fittest genomes are copied unchanged, the rest of the
+    /// generation is bred by `inner`.
+    Elitism {
+        count: usize,
+        inner: Box<Strategy>,
+    },
+}
+
+impl Default for Strategy {
+    fn default() -> Self {
+        Strategy::Pools(GenePoolAssembly)
+    }
+}
+
+impl EvolutionStrategy for Strategy {
+    fn breed(&mut self, population: &Population, count: usize) -> Vec<NetGenome> {
+        match self {
+            Strategy::Pools(s) => s.breed(population, count),
+            Strategy::Neat(s) => s.breed(population, count),
+            Strategy::Generational(s) => s.breed(population, count),
+            Strategy::MuPlusLambda(s) => s.breed(population, count),
+            Strategy::Novelty(s) => s.breed(population, count),
+            Strategy::MapElites(s) => s.breed(population, count),
+            Strategy::Nsga2(s) => s.breed(population, count),
+            Strategy::Elitism {
+                count: elites,
+                inner,
+            } => {
+                let all: Vec<usize> = (0..population.genomes.len()).collect();
+                let mut children: Vec<NetGenome> = population
+                    .ranked(&all)
+                    .into_iter()
+                    .take((*elites).min(count))
+                    .map(|i| population.genomes[i].clone())
+                    .collect();
+                children.extend(inner.breed(population, count - children.len()));
+                children
+            }
+        }
+    }
+}
+
+/// Parses `pools`, `neat`, `generational[:tournament[:size]|:roulette][:nearest]`,
+/// `mu+lambda:<mu>`, `novelty[:<descriptor>]`, `map-elites[:<descriptor>[:<bins>]]`
+/// `nsga2:<objective>,<objective>...` or `elitism:<count>:<strategy>`.
+impl FromStr for Strategy {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut args = s.split(':');
+        let number = |arg: Option<&str>, default: &str| {
+            let arg = arg.unwrap_or(default);
+            arg.parse::<usize>()
+                .map_err(|e| format!("{:?}: {}", arg, e))
+        };
+        match args.next().unwrap_or_default() {
+            "pools" => Ok(Strategy::Pools(GenePoolAssembly)),
+            "neat" => Ok(Strategy::Neat(Neat::default())),
+            "generational" => {
+                let mut ga = Generational::default();
+                while let Some(arg) = args.next() {
+                    match arg {
+                        "tournament" => {
+                            let size = args.clone().next().and_then(|n| n.parse().ok());
+                            if size.is_some() {
+                                args.next();
+                            }
+                            ga.selection = ParentSelection::Tournament {
+                                size: size.unwrap_or(3),
+                            };
+                        }
+                        "roulette" => ga.selection = ParentSelection::Roulette,
+                        "nearest" => ga.mating = Mating::Nearest,
+                        _ => return Err(format!("unknown generational option {:?}", arg)),
+                    }
+                }
+                Ok(Strategy::Generational(ga))
+            }
+            "mu+lambda" => Ok(Strategy::MuPlusLambda(MuPlusLambda {
+                mu: number(args.next(), "100")?,
+            })),
+            "novelty" => Ok(Strategy::Novelty(Novelty::new(
+                args.next().unwrap_or("position").parse()?,
+            ))),
+            "map-elites" => {
+                let descriptor = args.next().unwrap_or("position").parse()?;
+                Ok(Strategy::MapElites(MapElites::new(
+                    descriptor,
+                    number(args.next(), "10")?,
+                )))
+            }
+            "nsga2" => {
+                let objectives = args
+                    .next()
+                    .unwrap_or("survival,size")
+                    .split(',')
+                    .map(|o| o.parse())
+                    .collect::<Result<_, _>>()?;
+                Ok(Strategy::Nsga2(Nsga2::new(objectives)))
+            }
+            "elitism" => {
+                let count = number(args.next(), "10")?;
+                let inner = args.collect::<Vec<_>>().join(":");
+                let inner = if inner.is_empty() {
+                    Strategy::default()
+                } else {
+                    inner.parse()?
+                };
+                Ok(Strategy::Elitism {
+                    count,
+                    inner: Box::new(inner),
+                })
+            }
+            _ => Err(format!("unknown strategy {:?}", s)),
+        }
+    }
+}
+
+/// How children change pool once bred.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum PoolMigration {
+    /// Children stay in the pool of their first parent.
+    None,
+    /// Each child moves to another random pool with probability `rate`.
+    Random { rate: f32 },
+    /// Children get a mutated colour and join the pool it leans to, which is
+    /// how membership worked before it was explicit.
+    Colour,
+}
+
+impl Default for PoolMigration {
+    fn default() -> Self {
+        PoolMigration::None
+    }
+}
+
+/// Parses `none`, `random:<rate>` or `colour`.
+impl FromStr for PoolMigration {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':').unwrap_or((s, "")) {
+            ("none", "") => Ok(PoolMigration::None),
+            ("random", rate) => Ok(PoolMigration::Random {
+                rate: rate.parse().map_err(|e| format!("{:?}: {}", rate, e))?,
+            }),
+            ("colour" | "color", "") => Ok(PoolMigration::Colour),
+            _ => Err(format!("unknown pool migration {:?}", s)),
+        }
+    }
+}
+
+impl PoolMigration {
+    /// Moves `children` between the `pools` pools, returning how many
+    /// changed pool.
+    pub fn apply(&self, children: &mut [NetGenome], pools: usize) -> usize {
+        let mut moved = 0;
+        for child in children {
+            let pool = child.pool();
+            match self {
+                PoolMigration::None => {}
+                PoolMigration::Random { rate } => {
+                    if pools > 1 && random::<f32>() < *rate {
+                        child.pool = (pool + 1 + random::<usize>() % (pools - 1)) % pools;
+                    }
+                }
+                PoolMigration::Colour => {
+                    child.randomize_color();
+                    child.pool = child.color_pool().min(pools - 1);
+                }
+            }
+            moved += (child.pool() != pool) as usize;
+        }
+        moved
+    }
+}
@@ -1,7 +1,88 @@
+use std::{collections::HashSet, str::FromStr};
+
 use serde::{Deserialize, Serialize};
 
+use crate::coevolution::Scenario;
+
 #[derive(Default, Clone, Debug, Serialize, Deserialize)]
 pub struct World {
     pub width: i32,
     pub height: i32,
+    pub survival: SurvivalRule,
+    /// Co-evolution scenario pools are scored by, if any.
+    pub scenario: Option<Scenario>,
+    /// Cells no replicant can enter.
+    pub obstacles: HashSet<(i32, i32)>,
+}
+
+impl World {
+    /// Whether `(x, y)` is inside the world. Replicants never leave it, so
+    /// walls cannot be walked around.
+    pub fn contains(&self, (x, y): (i32, i32)) -> bool {
+        x >= 0 && x < self.width && y >= 0 && y < self.height
+    }
+    /// Cells a replicant can be placed on.
+    pub fn free_cells(&self) -> usize {
+        let blocked = self
+            .obstacles
+            .iter()
+            .filter(|cell| self.contains(**cell))
+            .count();
+        (self.width.max(0) as usize * self.height.max(0) as usize).saturating_sub(blocked)
+    }
+}
+
+/// Where replicants have to be to count as alive.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum SurvivalRule {
+    /// Right of `x` times the width.
+    Zone { x: f32 },
+    /// Inside the rectangle spanning the fractions `x` and `y` of the world.
+    Region { x: (f32, f32), y: (f32, f32) },
+    /// Pool 0 has to keep away from the other pools, the others have to be
+    /// surrounded by pools other than 0.
+    Crowd,
+}
+
+impl Default for SurvivalRule {
+    fn default() -> Self {
+        SurvivalRule::Zone { x: 0.5 }
+    }
+}
+
+/// Parses `zone[:<x>]`, `region:<x0>,<x1>,<y0>,<y1>` or `crowd`.
+impl FromStr for SurvivalRule {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, arg) = match s.split_once(':') {
+            Some((name, arg)) => (name, Some(arg)),
+            None => (s, None),
+        };
+        let number = |n: &str| {
+            n.trim()
+                .parse::<f32>()
+                .map_err(|e| format!("{:?}: {}", n, e))
+        };
+        match name {
+            "zone" => Ok(SurvivalRule::Zone {
+                x: number(arg.unwrap_or("0.5"))?,
+            }),
+            "region" => {
+                let bounds = arg
+                    .unwrap_or("")
+                    .split(',')
+                    .map(number)
+                    .collect::<Result<Vec<_>, _>>()?;
+                match bounds[..] {
+                    [x0, x1, y0, y1] => Ok(SurvivalRule::Region {
+                        x: (x0, x1),
+                        y: (y0, y1),
+                    }),
+                    _ => Err("expected region:<x0>,<x1>,<y0>,<y1>".to_string()),
+                }
+            }
+            "crowd" => Ok(SurvivalRule::Crowd),
+            _ => Err(format!("unknown survival rule {:?}", s)),
+        }
+    }
 }